@@ -0,0 +1,435 @@
+extern crate proc_macro2;
+
+use proc_macro2::Span;
+use syn::ext::IdentExt;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{bracketed, parenthesized, token, Error, Ident, Lit, LitStr, Meta, Path, Result, Token};
+
+use crate::local::Local;
+use crate::marker::{check_kind, Marker};
+use crate::mode::Mode;
+use crate::naming::Collision;
+use crate::reexport::{parse_crate_path, parse_internal_path};
+
+// The names of all valid arguments.
+pub const ARGS: &[&str] = &[
+    "mode",
+    "always",
+    "cfg",
+    "impl",
+    "internal",
+    "internal_path",
+    "crate_path",
+    "local",
+    "qualify_self",
+    "qualify",
+    "calls",
+    "local_inner_macros",
+    "split",
+    "seal",
+    "warn_direct",
+    "collision",
+];
+
+// Arguments that can be given more than once.
+const REPEATABLE_ARGS: &[&str] = &["impl", "calls"];
+
+// Pairs of arguments that set the same thing, and can't be given together.
+const CONFLICTING_ARGS: &[(&str, &str)] = &[("mode", "always")];
+
+// A single argument, as written in the attribute.
+pub enum RawArg {
+    // `name`, `name = value`, `name = [value, ...]`, `name = [ident, ...]` or
+    // `name(...)`
+    Named(Ident, RawValue),
+    // A literal on its own, which is never valid
+    Lit(Lit),
+}
+
+pub enum RawValue {
+    Flag,
+    Lit(Lit),
+    Array(Span, Vec<Lit>),
+    Idents(Span, Vec<Ident>),
+    List(Span, Vec<RawArg>),
+}
+
+impl Parse for RawArg {
+    fn parse(input: ParseStream) -> Result<Self> {
+        if input.peek(Lit) {
+            return input.parse().map(RawArg::Lit);
+        }
+
+        let name = input.call(Ident::parse_any)?;
+        let value = if input.peek(Token![=]) {
+            input.parse::<Token![=]>()?;
+            if input.peek(token::Bracket) {
+                let content;
+                let bracket = bracketed!(content in input);
+                if content.peek(Ident) {
+                    let idents = Punctuated::<Ident, Token![,]>::parse_terminated(&content)?;
+                    RawValue::Idents(bracket.span, idents.into_iter().collect())
+                } else {
+                    let values = Punctuated::<Lit, Token![,]>::parse_terminated(&content)?;
+                    RawValue::Array(bracket.span, values.into_iter().collect())
+                }
+            } else {
+                RawValue::Lit(input.parse()?)
+            }
+        } else if input.peek(token::Paren) {
+            let content;
+            let paren = parenthesized!(content in input);
+            let args = Punctuated::<RawArg, Token![,]>::parse_terminated(&content)?;
+            RawValue::List(paren.span, args.into_iter().collect())
+        } else {
+            RawValue::Flag
+        };
+
+        Ok(RawArg::Named(name, value))
+    }
+}
+
+// The arguments of a single `#[clean_docs(...)]` attribute. Every field is
+// optional, so that arguments can be merged with defaults.
+#[derive(Clone, Default)]
+pub struct Args {
+    pub mode: Option<Mode>,
+    pub cfg: Option<Meta>,
+    pub priv_markers: Option<Vec<Marker>>,
+    pub priv_ident: Option<LitStr>,
+    pub internal_path: Option<Path>,
+    pub crate_path: Option<Path>,
+    pub local: Option<Local>,
+    pub qualify_self: Option<bool>,
+    pub qualify: Option<Vec<Ident>>,
+    // Other macros whose internal rules are called, with their internal names
+    // if given
+    pub calls: Option<Vec<(Ident, Option<LitStr>)>>,
+    pub local_inner_macros: Option<bool>,
+    pub split: Option<bool>,
+    pub seal: Option<bool>,
+    pub warn_direct: Option<bool>,
+    pub collision: Option<Collision>,
+}
+
+impl Args {
+    // Parse the arguments, reporting every invalid argument at once.
+    pub fn parse(args: Vec<RawArg>) -> Result<Self> {
+        let mut parsed = Args::default();
+        let mut seen = Vec::new();
+        let mut errors: Option<Error> = None;
+
+        for arg in args {
+            if let Err(err) = parsed.parse_arg(arg, &mut seen) {
+                match &mut errors {
+                    Some(errors) => errors.combine(err),
+                    None => errors = Some(err),
+                }
+            }
+        }
+
+        match errors {
+            Some(errors) => Err(errors),
+            None => Ok(parsed),
+        }
+    }
+
+    fn parse_arg(&mut self, arg: RawArg, seen: &mut Vec<String>) -> Result<()> {
+        let (ident, value) = match arg {
+            RawArg::Named(ident, value) => (ident, value),
+            RawArg::Lit(lit) => {
+                return Err(Error::new_spanned(lit, "expected `name = value` or `name`"))
+            }
+        };
+
+        let name = ident.to_string();
+
+        if !ARGS.contains(&name.as_str()) {
+            let msg = match closest_match(&name, ARGS) {
+                Some(closest) => {
+                    format!("unknown argument `{}`, did you mean `{}`?", name, closest)
+                }
+                None => format!("unknown argument `{}`", name),
+            };
+            return Err(Error::new_spanned(ident, msg));
+        }
+        if seen.contains(&name) && !REPEATABLE_ARGS.contains(&name.as_str()) {
+            return Err(Error::new_spanned(
+                ident,
+                format!("duplicate argument `{}`", name),
+            ));
+        }
+        let conflicting = CONFLICTING_ARGS
+            .iter()
+            .find_map(|&(a, b)| {
+                if name == a {
+                    Some(b)
+                } else if name == b {
+                    Some(a)
+                } else {
+                    None
+                }
+            })
+            .filter(|other| seen.iter().any(|seen| seen == other));
+        if let Some(other) = conflicting {
+            return Err(Error::new_spanned(
+                ident,
+                format!("conflicting arguments `{}` and `{}`", other, name),
+            ));
+        }
+        seen.push(name.clone());
+
+        match name.as_str() {
+            "mode" => {
+                let val = str_value(&ident, value)?;
+                self.mode = Some(
+                    val.value()
+                        .parse()
+                        .map_err(|err| Error::new(val.span(), err))?,
+                );
+            }
+            "always" => {
+                let always = bool_value(value)?;
+                self.mode = Some(if always { Mode::Always } else { Mode::Doc });
+            }
+            "cfg" => {
+                let val = str_value(&ident, value)?;
+                self.cfg = Some(
+                    val.parse()
+                        .map_err(|_| Error::new(val.span(), "expected cfg predicate"))?,
+                );
+            }
+            "impl" => {
+                // `impl = "..."` is a prefix marker, other kinds are given as
+                // `impl(kind = "...")`
+                let priv_markers = match value {
+                    RawValue::List(_, kinds) => parse_markers(kinds)?,
+                    value => str_values(&ident, value)?
+                        .iter()
+                        .map(|val| marker("prefix", val))
+                        .collect::<Result<_>>()?,
+                };
+                self.priv_markers
+                    .get_or_insert_with(Vec::new)
+                    .extend(priv_markers);
+            }
+            "internal" => self.priv_ident = Some(str_value(&ident, value)?),
+            "internal_path" => {
+                let val = str_value(&ident, value)?;
+                self.internal_path = Some(
+                    parse_internal_path(&val.value()).map_err(|err| Error::new(val.span(), err))?,
+                );
+            }
+            "crate_path" => {
+                let val = str_value(&ident, value)?;
+                self.crate_path = Some(
+                    parse_crate_path(&val.value()).map_err(|err| Error::new(val.span(), err))?,
+                );
+            }
+            "local" => {
+                let val = str_value(&ident, value)?;
+                self.local = Some(
+                    val.value()
+                        .parse()
+                        .map_err(|err| Error::new(val.span(), err))?,
+                );
+            }
+            "qualify_self" => self.qualify_self = Some(bool_value(value)?),
+            "qualify" => {
+                self.qualify = Some(ident_values(&ident, value)?);
+            }
+            "calls" => {
+                let calls = match value {
+                    RawValue::List(_, calls) => parse_calls(calls)?,
+                    _ => return Err(Error::new_spanned(ident, "expected `calls(name, ...)`")),
+                };
+                self.calls.get_or_insert_with(Vec::new).extend(calls);
+            }
+            "local_inner_macros" => self.local_inner_macros = Some(bool_value(value)?),
+            "split" => self.split = Some(bool_value(value)?),
+            "seal" => self.seal = Some(bool_value(value)?),
+            "warn_direct" => self.warn_direct = Some(bool_value(value)?),
+            "collision" => {
+                let val = str_value(&ident, value)?;
+                self.collision = Some(
+                    val.value()
+                        .parse()
+                        .map_err(|err| Error::new(val.span(), err))?,
+                );
+            }
+            _ => unreachable!(),
+        }
+
+        Ok(())
+    }
+
+    // Fill in every argument that wasn't given with the one from `defaults`.
+    // Setting `cfg` without `mode` implies `mode = "cfg"`, even if `defaults`
+    // has a different mode.
+    pub fn with_defaults(self, defaults: &Args) -> Self {
+        let mode = match (self.mode, &self.cfg) {
+            (Some(mode), _) => Some(mode),
+            (None, Some(_)) => Some(Mode::Cfg),
+            (None, None) => defaults.mode,
+        };
+
+        Args {
+            mode,
+            cfg: self.cfg.or_else(|| defaults.cfg.clone()),
+            priv_markers: self.priv_markers.or_else(|| defaults.priv_markers.clone()),
+            priv_ident: self.priv_ident.or_else(|| defaults.priv_ident.clone()),
+            internal_path: self
+                .internal_path
+                .or_else(|| defaults.internal_path.clone()),
+            crate_path: self.crate_path.or_else(|| defaults.crate_path.clone()),
+            local: self.local.or(defaults.local),
+            qualify_self: self.qualify_self.or(defaults.qualify_self),
+            // Macros to qualify are merged, rather than overridden
+            qualify: match (self.qualify, &defaults.qualify) {
+                (Some(mut qualify), Some(default)) => {
+                    qualify.extend(default.iter().cloned());
+                    Some(qualify)
+                }
+                (qualify, default) => qualify.or_else(|| default.clone()),
+            },
+            calls: self.calls.or_else(|| defaults.calls.clone()),
+            local_inner_macros: self.local_inner_macros.or(defaults.local_inner_macros),
+            split: self.split.or(defaults.split),
+            seal: self.seal.or(defaults.seal),
+            warn_direct: self.warn_direct.or(defaults.warn_direct),
+            collision: self.collision.or(defaults.collision),
+        }
+    }
+}
+
+fn str_value(ident: &Ident, value: RawValue) -> Result<LitStr> {
+    match value {
+        RawValue::Lit(Lit::Str(val)) => Ok(val),
+        RawValue::Lit(val) => Err(Error::new_spanned(val, "expected string")),
+        RawValue::Array(span, _) | RawValue::Idents(span, _) => {
+            Err(Error::new(span, "expected string"))
+        }
+        RawValue::Flag | RawValue::List(..) => {
+            Err(Error::new_spanned(ident, "expected `name = \"...\"`"))
+        }
+    }
+}
+
+// A string, or an array of strings.
+fn str_values(ident: &Ident, value: RawValue) -> Result<Vec<LitStr>> {
+    match value {
+        RawValue::Array(_, vals) => vals
+            .into_iter()
+            .map(|val| match val {
+                Lit::Str(val) => Ok(val),
+                val => Err(Error::new_spanned(val, "expected string")),
+            })
+            .collect(),
+        value => str_value(ident, value).map(|val| vec![val]),
+    }
+}
+
+// An array of identifiers, or of strings containing identifiers.
+fn ident_values(ident: &Ident, value: RawValue) -> Result<Vec<Ident>> {
+    match value {
+        RawValue::Idents(_, idents) => Ok(idents),
+        RawValue::Array(_, vals) => vals
+            .into_iter()
+            .map(|val| match val {
+                Lit::Str(val) => val.parse(),
+                val => Err(Error::new_spanned(val, "expected identifier")),
+            })
+            .collect(),
+        RawValue::Lit(val) => Err(Error::new_spanned(val, "expected `[...]`")),
+        RawValue::Flag | RawValue::List(..) => {
+            Err(Error::new_spanned(ident, "expected `name = [...]`"))
+        }
+    }
+}
+
+// Parse the `kind = "..."` arguments of `impl(...)`.
+fn parse_markers(args: Vec<RawArg>) -> Result<Vec<Marker>> {
+    let mut priv_markers = Vec::new();
+
+    for arg in args {
+        let (ident, value) = match arg {
+            RawArg::Named(ident, value) => (ident, value),
+            RawArg::Lit(lit) => return Err(Error::new_spanned(lit, "expected `kind = \"...\"`")),
+        };
+
+        let kind = ident.to_string();
+        check_kind(&kind).map_err(|err| Error::new_spanned(&ident, err))?;
+
+        for val in str_values(&ident, value)? {
+            priv_markers.push(marker(&kind, &val)?);
+        }
+    }
+
+    Ok(priv_markers)
+}
+
+// Parse the `name` and `name = "..."` arguments of `calls(...)`.
+fn parse_calls(args: Vec<RawArg>) -> Result<Vec<(Ident, Option<LitStr>)>> {
+    args.into_iter()
+        .map(|arg| match arg {
+            RawArg::Named(ident, RawValue::Flag) => Ok((ident, None)),
+            RawArg::Named(ident, value) => {
+                let val = str_value(&ident, value)?;
+                Ok((ident, Some(val)))
+            }
+            RawArg::Lit(lit) => Err(Error::new_spanned(
+                lit,
+                "expected `name` or `name = \"...\"`",
+            )),
+        })
+        .collect()
+}
+
+fn marker(kind: &str, val: &LitStr) -> Result<Marker> {
+    Marker::new(kind, &val.value()).map_err(|err| Error::new(val.span(), err))
+}
+
+// Flags can be given as `name` or `name = true|false`.
+fn bool_value(value: RawValue) -> Result<bool> {
+    match value {
+        RawValue::Flag => Ok(true),
+        RawValue::Lit(Lit::Bool(val)) => Ok(val.value),
+        RawValue::Lit(val) => Err(Error::new_spanned(val, "expected `true` or `false`")),
+        RawValue::Array(span, _) | RawValue::Idents(span, _) | RawValue::List(span, _) => {
+            Err(Error::new(span, "expected `true` or `false`"))
+        }
+    }
+}
+
+// Find the candidate closest to `name`, if any is close enough to be a typo.
+pub fn closest_match<'a>(name: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(|candidate| (edit_distance(name, candidate), *candidate))
+        .filter(|(distance, _)| *distance <= std::cmp::max(name.len(), 3) / 3)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, candidate)| candidate)
+}
+
+// Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, a_char) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let cur = row[j + 1];
+            row[j + 1] = if a_char == *b_char {
+                prev
+            } else {
+                1 + prev.min(row[j]).min(cur)
+            };
+            prev = cur;
+        }
+    }
+
+    row[b.len()]
+}
@@ -0,0 +1,239 @@
+extern crate proc_macro2;
+
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, quote_spanned, ToTokens, TokenStreamExt};
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::token::Brace;
+use syn::{
+    braced, parenthesized, Attribute, Error, Ident, MacroDelimiter, Result, Token, Visibility,
+};
+
+use crate::args::{Args, RawArg, ARGS};
+use crate::macro_rules::{macro_delimiter_surround, MacroRulesRule};
+use crate::marker::{default_markers, Classifier};
+use crate::mode::Mode;
+use crate::naming::{expand_template, DEFAULT_TEMPLATE};
+use crate::other_calls;
+use crate::replace_macro_invocs::{replace_macro_invocs, Rewrites};
+
+// A `macro` item, from the unstable `decl_macro` feature.
+#[derive(Clone)]
+pub struct DeclMacro {
+    pub attrs: Vec<Attribute>,
+    pub vis: Visibility,
+    pub macro_token: Token![macro],
+    pub ident: Ident,
+    pub body: DeclMacroBody,
+}
+
+#[derive(Clone)]
+pub enum DeclMacroBody {
+    // `macro name { (...) => { ... }, ... }`
+    Arms(Brace, Punctuated<MacroRulesRule, Token![,]>),
+    // `macro name(...) { ... }`, kept as a rule so that it's classified like
+    // one
+    Single(MacroRulesRule),
+}
+
+impl Parse for DeclMacro {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        let macro_token = input.parse()?;
+        let ident = input.parse()?;
+
+        let body = if input.peek(Brace) {
+            let content;
+            let brace = braced!(content in input);
+            DeclMacroBody::Arms(
+                brace,
+                Punctuated::<MacroRulesRule, Token![,]>::parse_terminated(&content)?,
+            )
+        } else {
+            let rule;
+            let paren = parenthesized!(rule in input);
+            let body;
+            let brace = braced!(body in input);
+            DeclMacroBody::Single(MacroRulesRule {
+                annotation: None,
+                rule_delimiter: MacroDelimiter::Paren(paren),
+                rule: rule.parse()?,
+                fat_arrow: Default::default(),
+                body_delimiter: MacroDelimiter::Brace(brace),
+                body: body.parse()?,
+            })
+        };
+
+        Ok(DeclMacro {
+            attrs,
+            vis,
+            macro_token,
+            ident,
+            body,
+        })
+    }
+}
+
+impl ToTokens for DeclMacro {
+    fn to_tokens(&self, tokens: &mut TokenStream) {
+        tokens.append_all(&self.attrs);
+        self.vis.to_tokens(tokens);
+        self.macro_token.to_tokens(tokens);
+        self.ident.to_tokens(tokens);
+        match &self.body {
+            DeclMacroBody::Arms(brace, rules) => {
+                brace.surround(tokens, |tokens| rules.to_tokens(tokens));
+            }
+            DeclMacroBody::Single(rule) => {
+                macro_delimiter_surround(&rule.rule_delimiter, tokens, |tokens| {
+                    rule.rule.to_tokens(tokens)
+                });
+                macro_delimiter_surround(&rule.body_delimiter, tokens, |tokens| {
+                    rule.body.to_tokens(tokens)
+                });
+            }
+        }
+    }
+}
+
+// The arguments that apply to `macro` items. The others are about exporting
+// `macro_rules!` macros, which `macro` items don't need.
+const SUPPORTED_ARGS: &[&str] = &["mode", "always", "cfg", "impl", "internal", "calls"];
+
+// Report each argument given to a `macro` item that doesn't apply to it.
+// Unknown arguments are left to `Args::parse`.
+pub fn check_decl_macro_args(args: &[RawArg]) -> Result<()> {
+    let mut errors: Option<Error> = None;
+    for arg in args {
+        let ident = match arg {
+            RawArg::Named(ident, _) => ident,
+            RawArg::Lit(_) => continue,
+        };
+        let name = ident.to_string();
+        if !ARGS.contains(&name.as_str()) || SUPPORTED_ARGS.contains(&name.as_str()) {
+            continue;
+        }
+        let err = Error::new_spanned(
+            ident,
+            format!("`{}` isn't supported for `macro` items", name),
+        );
+        match &mut errors {
+            Some(errors) => errors.combine(err),
+            None => errors = Some(err),
+        }
+    }
+
+    match errors {
+        Some(errors) => Err(errors),
+        None => Ok(()),
+    }
+}
+
+// Clean a `macro` item. Names in the body of a `macro` resolve where the macro
+// is defined, so the internal macro is called by name, and emitted with the
+// same visibility. No local copy is needed.
+pub fn clean_decl_macro(args: Args, decl_macro: DeclMacro) -> TokenStream {
+    let Args {
+        mode,
+        cfg,
+        priv_markers,
+        priv_ident,
+        calls,
+        ..
+    } = args;
+
+    let mode = match Mode::resolve(mode, &cfg) {
+        Ok(mode) => mode,
+        Err(err) => {
+            return quote! {
+                compile_error!(#err);
+            }
+        }
+    };
+
+    // A single-arm macro has no internal rules
+    let rules = match &decl_macro.body {
+        DeclMacroBody::Arms(_, rules) if mode.runs() => rules,
+        _ => return decl_macro.into_token_stream(),
+    };
+
+    let pub_ident = &decl_macro.ident;
+    let (template, template_span) = match &priv_ident {
+        Some(priv_ident) => (priv_ident.value(), priv_ident.span()),
+        None => (DEFAULT_TEMPLATE.to_string(), Span::call_site()),
+    };
+    let priv_ident = match expand_template(&template, pub_ident, &decl_macro.to_token_stream()) {
+        Ok(priv_ident) => priv_ident,
+        Err(err) => {
+            return quote_spanned! {
+                template_span=> compile_error!(#err);
+            }
+        }
+    };
+    let other_calls = match other_calls(calls, &template, |_, _| None) {
+        Ok(other_calls) => other_calls,
+        Err(err) => return err,
+    };
+
+    let classifier = Classifier::new(priv_markers.unwrap_or_else(default_markers), rules);
+    let rewrites = Rewrites {
+        pub_ident,
+        priv_ident: &priv_ident,
+        priv_path: None,
+        self_path: None,
+        qualify: Vec::new(),
+        calls: other_calls,
+        split: &[],
+        seal: None,
+        classifier: &classifier,
+    };
+
+    let mut pub_rules = Punctuated::<MacroRulesRule, Token![,]>::new();
+    let mut priv_rules = Punctuated::<MacroRulesRule, Token![,]>::new();
+    for rule in rules {
+        let mut rule = rule.clone();
+        rule.body = replace_macro_invocs(rule.body, &rewrites);
+        if classifier.is_internal_rule(&rule) {
+            priv_rules.push(rule);
+        } else {
+            pub_rules.push(rule);
+        }
+    }
+
+    if pub_rules.is_empty() {
+        return quote! {
+            compile_error!("no public rules");
+        };
+    }
+
+    if priv_rules.is_empty() {
+        return decl_macro.into_token_stream();
+    }
+
+    if rules.trailing_punct() {
+        priv_rules.push_punct(<Token![,]>::default());
+        pub_rules.push_punct(<Token![,]>::default());
+    }
+
+    let brace = Brace::default();
+    let mut pub_macro = decl_macro.clone();
+    pub_macro.body = DeclMacroBody::Arms(brace, pub_rules);
+
+    // The internal macro keeps the visibility, but not the docs
+    let mut priv_macro = pub_macro.clone();
+    priv_macro.ident = priv_ident;
+    priv_macro.body = DeclMacroBody::Arms(brace, priv_rules);
+    priv_macro.attrs.retain(|attr| !attr.path.is_ident("doc"));
+
+    let (gate, fallback) = mode.gate(cfg, &[decl_macro.into_token_stream()]);
+    quote! {
+        #gate
+        #pub_macro
+        #gate
+        #[doc(hidden)]
+        #priv_macro
+
+        #fallback
+    }
+}
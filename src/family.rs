@@ -0,0 +1,183 @@
+extern crate proc_macro2;
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{Attribute, Error, Ident, LitStr, Result};
+
+use crate::args::Args;
+use crate::clean_macro_rules;
+use crate::macro_rules::{MacroRules, RuleAnnotation};
+use crate::marker::{default_markers, Classifier};
+use crate::module::{is_clean_docs_attr, take_args};
+use crate::reexport::check_internal_path;
+
+// The input of `clean_docs_family!`: an optional `#![clean_docs(...)]`, an
+// `#[internal]` macro with the shared internal rules, and the public macros.
+pub struct Family {
+    attrs: Vec<Attribute>,
+    macros: Vec<MacroRules>,
+}
+
+impl Parse for Family {
+    fn parse(input: ParseStream) -> Result<Self> {
+        let attrs = input.call(Attribute::parse_inner)?;
+        let mut macros = Vec::new();
+        while !input.is_empty() {
+            macros.push(input.parse()?);
+        }
+        Ok(Family { attrs, macros })
+    }
+}
+
+// The internal macro shared by a family, as seen by each of its macros.
+pub struct Shared {
+    // The name of the `#[internal]` macro, which the internal macro keeps
+    pub ident: Ident,
+    // The `#[internal]` macro as written, to be emitted when not running. Only
+    // the first macro of the family has it, and emits the internal macro.
+    pub untouched: Option<TokenStream>,
+}
+
+// Clean every macro of a family, sending all of their internal calls to the
+// shared internal macro.
+pub fn clean_family(defaults: Args, family: Family) -> TokenStream {
+    match try_clean_family(defaults, family) {
+        Ok(cleaned) => cleaned,
+        Err(err) => err.to_compile_error(),
+    }
+}
+
+fn try_clean_family(defaults: Args, family: Family) -> Result<TokenStream> {
+    let Family { mut attrs, macros } = family;
+    let mut args = take_args(&mut attrs, &defaults)?;
+    check_internal_path(&args, &defaults)?;
+    if let Some(attr) = attrs.first() {
+        return Err(Error::new_spanned(attr, "expected `#![clean_docs(...)]`"));
+    }
+
+    let (mut internal, members): (Vec<_>, Vec<_>) = macros
+        .into_iter()
+        .partition(|mac_rules| mac_rules.attrs.iter().any(is_internal_attr));
+    let mut shared = match internal.len() {
+        0 => {
+            return Err(Error::new(
+                Span::call_site(),
+                "expected an `#[internal]` macro with the shared internal rules",
+            ))
+        }
+        1 => internal.remove(0),
+        _ => {
+            return Err(Error::new_spanned(
+                &internal[1].ident,
+                "only one macro of a family can be `#[internal]`",
+            ))
+        }
+    };
+    if members.is_empty() {
+        return Err(Error::new_spanned(
+            &shared.ident,
+            "expected at least one public macro",
+        ));
+    }
+
+    // Every rule of the `#[internal]` macro is internal, and only those are
+    let exported = is_exported(&members[0]);
+    let priv_markers = args.priv_markers.clone().unwrap_or_else(default_markers);
+    for mac_rules in &members {
+        if let Some(attr) = mac_rules.attrs.iter().find(|attr| is_clean_docs_attr(attr)) {
+            return Err(Error::new_spanned(
+                attr,
+                "arguments are given for the whole family, as `#![clean_docs(...)]`",
+            ));
+        }
+        if is_exported(mac_rules) != exported {
+            return Err(Error::new_spanned(
+                &mac_rules.ident,
+                "either every macro of a family is exported, or none is",
+            ));
+        }
+        let classifier = Classifier::new(priv_markers.clone(), &mac_rules.rules);
+        if let Some(rule) = mac_rules
+            .rules
+            .iter()
+            .find(|rule| classifier.is_internal_rule(rule))
+        {
+            return Err(Error::new_spanned(
+                &rule.rule,
+                "internal rules of a family go in its `#[internal]` macro",
+            ));
+        }
+    }
+    shared.attrs.retain(|attr| !is_internal_attr(attr));
+    for rule in shared.rules.iter_mut() {
+        rule.annotation.get_or_insert(RuleAnnotation::Internal);
+    }
+
+    // Calls to any macro of the family, and to the `#[internal]` macro itself,
+    // go to the internal macro. It keeps its name, so that the local copies of
+    // the macros can still call it.
+    let priv_name = LitStr::new(&shared.ident.to_string(), shared.ident.span());
+    let calls = args.calls.get_or_insert_with(Vec::new);
+    for ident in members
+        .iter()
+        .map(|mac_rules| &mac_rules.ident)
+        .chain(Some(&shared.ident))
+    {
+        calls.push((ident.clone(), Some(priv_name.clone())));
+    }
+    args.qualify
+        .get_or_insert_with(Vec::new)
+        .extend(members.iter().map(|mac_rules| mac_rules.ident.clone()));
+
+    let export = if exported {
+        quote!(#[macro_export])
+    } else {
+        quote!()
+    };
+    let mut untouched = Some(quote! {
+        #[doc(hidden)]
+        #[allow(unused_macros)]
+        #export
+        #shared
+    });
+
+    // Each macro is cleaned with the shared rules before its own
+    let mut cleaned = TokenStream::new();
+    for mut mac_rules in members {
+        let mut rules = Punctuated::new();
+        for rule in shared.rules.iter().chain(&mac_rules.rules) {
+            rules.push(rule.clone());
+        }
+        if mac_rules.rules.trailing_punct() {
+            rules.push_punct(Default::default());
+        }
+        mac_rules.rules = rules;
+
+        let shared = Shared {
+            ident: shared.ident.clone(),
+            untouched: untouched.take(),
+        };
+        cleaned.extend(clean_macro_rules(
+            args.clone(),
+            mac_rules,
+            // Without `internal_path`, there's nothing to re-export
+            &mut Vec::new(),
+            Some(shared),
+        ));
+    }
+
+    Ok(cleaned)
+}
+
+fn is_internal_attr(attr: &Attribute) -> bool {
+    attr.path.is_ident("internal") && attr.tokens.is_empty()
+}
+
+fn is_exported(mac_rules: &MacroRules) -> bool {
+    mac_rules
+        .attrs
+        .iter()
+        .any(|attr| attr.path.is_ident("macro_export"))
+}
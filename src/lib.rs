@@ -71,19 +71,260 @@
 //!
 //! By default this transformation only happens when `rustdoc` is building the
 //! documentation for your macro, so `clean_docs` shouldn't affect your normal
-//! compilation times (see [`always`](#always)).
+//! compilation times (see [`mode`](#mode)).
+//!
+//! # Macros that aren't exported
+//! A macro without `#[macro_export]` isn't part of the crate's public documentation, so
+//! `clean_docs` leaves it as written. Its internal macro couldn't be called through
+//! `$crate::`, and a call like `mac!(@impl ...)` only reaches an internal macro next to
+//! it from the module where it's defined, not from the modules that import `mac!`.
+//!
+//! ```
+//! mod module {
+//!     use clean_macro_docs::clean_docs;
+//!
+//!     #[clean_docs]
+//!     macro_rules! mac {
+//!         (@impl $e:expr) => {
+//!             format!("{}", $e)
+//!         };
+//!         ($e:expr) => {
+//!             $crate::module::mac!(@impl $e)
+//!         };
+//!     }
+//!
+//!     pub(crate) use mac;
+//! }
+//!
+//! # fn main() {
+//! assert_eq!(module::mac!(1), "1");
+//! # }
+//! ```
+//!
+//! # Annotating rules
+//! A rule can be marked as internal or public with `#[internal]` or `#[public]`,
+//! regardless of its flag (see [`impl`](#impl)). The annotations are removed
+//! from the generated macros.
+//!
+//! Calls to an annotated rule are recognized by the tokens the rule starts
+//! with, up to its first metavariable. An `#[internal]` rule has to start with
+//! some, like `(parse $e:expr)`.
+//!
+//! ```
+//! # use clean_macro_docs::clean_docs;
+//! #[clean_docs]
+//! #[macro_export]
+//! macro_rules! mac {
+//!     #[internal]
+//!     (parse $e:expr) => {
+//!         format!("{}", $e)
+//!     };
+//!     #[public]
+//!     (@verbose $e:expr) => {
+//!         format!("verbose: {}", mac!(parse $e))
+//!     };
+//!     ($e:expr) => {
+//!         mac!(parse $e)
+//!     };
+//! }
+//! ```
+//!
+//! # Modules
+//! `clean_docs` can also be applied to an inline module, in which case every
+//! `macro_rules!` macro in the module (and in its nested inline modules) is
+//! transformed. The module's arguments are used as defaults, and an individual
+//! macro can override them with its own `#[clean_docs(...)]` attribute.
+//! When set for a whole module, [`internal`](#internal) must contain `{name}` or `{hash}`.
+//!
+//! ```
+//! # use clean_macro_docs::clean_docs;
+//! #[clean_docs(impl = "#internal")]
+//! mod macros {
+//!     #[macro_export]
+//!     macro_rules! mac {
+//!         (#internal $e:expr) => {
+//!             format!("{}", $e)
+//!         };
+//!         ($e:expr) => {
+//!             mac!(#internal $e)
+//!         };
+//!     }
+//!
+//!     #[clean_docs(impl = "@")]
+//!     #[macro_export]
+//!     macro_rules! other_mac {
+//!         (@impl $e:expr) => {
+//!             format!("{}", $e)
+//!         };
+//!         ($e:expr) => {
+//!             other_mac!(@impl $e)
+//!         };
+//!     }
+//! }
+//! ```
+//!
+//! # Macro families
+//! Several macros can share one set of internal rules with `clean_docs_family!`. The
+//! shared rules go in a macro marked `#[internal]`, and every other macro in the block
+//! gets them too. Calls to the internal rules of any macro in the family, or to the
+//! `#[internal]` macro itself, go to a single hidden internal macro, which keeps the
+//! `#[internal]` macro's name. Arguments for the whole family are given as an inner
+//! `#![clean_docs(...)]` attribute.
+//!
+//! ```
+//! clean_macro_docs::clean_docs_family! {
+//!     #![clean_docs(impl = "@")]
+//!
+//!     #[internal]
+//!     macro_rules! __fmt {
+//!         (@fmt $e:expr) => {
+//!             format!("<{}>", $e)
+//!         };
+//!     }
+//!
+//!     /// Formats `$e`.
+//!     #[macro_export]
+//!     macro_rules! fmt_one {
+//!         ($e:expr) => {
+//!             fmt_one!(@fmt $e)
+//!         };
+//!     }
+//!
+//!     /// Formats `$a` and `$b`.
+//!     #[macro_export]
+//!     macro_rules! fmt_two {
+//!         ($a:expr, $b:expr) => {
+//!             fmt_two!(@fmt $a) + &__fmt!(@fmt $b)
+//!         };
+//!     }
+//! }
+//! ```
+//!
+//! The public macros can't have internal rules of their own, and either all of them are
+//! exported or none are. [`collision = "rename"`](#collision) doesn't apply, since the
+//! internal macro is named explicitly.
+//!
+//! # Macros generated by other macros
+//! `clean_docs` can be used on a `macro_rules!` macro that another macro defines. Since
+//! `macro_rules!` can't escape `$`, the usual trick is to pass it in as a `tt`:
+//!
+//! ```
+//! # use clean_macro_docs::clean_docs;
+//! macro_rules! make_fmt {
+//!     ($d:tt $name:ident, $prefix:expr) => {
+//!         #[clean_docs]
+//!         #[macro_export]
+//!         macro_rules! $name {
+//!             (@impl $d e:expr) => {
+//!                 format!("{}{}", $prefix, $d e)
+//!             };
+//!             ($d e:expr) => {
+//!                 $crate::$name!(@impl $d e)
+//!             };
+//!         }
+//!     };
+//! }
+//!
+//! make_fmt!($ fmt_a, "a: ");
+//! make_fmt!($ fmt_b, "b: ");
+//! ```
+//!
+//! `$crate`, and fragments like `$prefix:expr` or a rule body given as `$body:block`,
+//! are handled like in a macro written by hand.
+//!
+//! A macro-expanded `#[macro_export]` macro can't be called by absolute path from the
+//! crate that defines it, like `crate::fmt_a!(...)`. This is true of every exported
+//! macro that `clean_docs` emits, generated or not, and is why the crate uses the
+//! [local copy](#local) instead. Within the crate, call the macro by name after its
+//! definition; other crates can call it by any path.
+//!
+//! # `macro` items
+//! With the `decl-macro` feature, `clean_docs` can also be applied to `macro` items from
+//! the unstable `decl_macro` feature (macros 2.0), on their own or in a module:
+//!
+//! ```ignore
+//! #![feature(decl_macro)]
+//! # use clean_macro_docs::clean_docs;
+//!
+//! #[clean_docs]
+//! pub macro mac {
+//!     (@impl $e:expr) => {
+//!         format!("{}", $e)
+//!     },
+//!     ($e:expr) => {
+//!         mac!(@impl $e)
+//!     },
+//! }
+//! ```
+//!
+//! Internal rules are found the same way as for `macro_rules!`, and moved to a
+//! `#[doc(hidden)]` internal `macro` with the same visibility. Names in a `macro` resolve
+//! where it is defined, so internal calls use the internal macro's name, and no local copy
+//! is needed. `impl`, `internal`, `calls`, `mode`, `always` and `cfg` work as for
+//! `macro_rules!`; the other arguments are an error when given for a `macro` item, and
+//! ignored when given for a module or in `Cargo.toml`. A single-arm `macro mac(...) { ... }`
+//! has no internal rules, and is left as it is.
+//!
+//! # Defaults in `Cargo.toml`
+//! Defaults for a whole crate can be set in the crate's `Cargo.toml`. Module
+//! and macro arguments take precedence over them.
+//!
+//! ```toml
+//! [package.metadata.clean-macro-docs]
+//! mode = "cfg"
+//! cfg = "docsrs"
+//! impl = "#internal"
+//! internal = "__{name}_impl"
+//! ```
+//!
+//! All values are strings, with the same meaning as the arguments below, except
+//! for `qualify_self`, `local_inner_macros`, `split`, `seal` and `warn_direct`, which
+//! are booleans, and `qualify`, which is an array of strings. `impl` can also be a
+//! table of `kind = "..."` flags, or an array of strings and tables. As with modules,
+//! `internal` must contain `{name}` or `{hash}`. `internal_path` can only be set for a
+//! module.
 //!
 //! # Arguments
 //! You can use these optional arguments to configure `clean_macro`.
 //!
 //! ```
 //! # use clean_macro_docs::clean_docs;
-//! #[clean_docs(impl = "#internal", internal = "__internal_mac", always = true)]
+//! #[clean_docs(impl = "#internal", internal = "__internal_mac", mode = "always")]
 //! # macro_rules! mac { () => {} }
 //! ```
 //!
 //! ## `impl`
 //! A string representing the "flag" at the begining of an internal rule. Defaults to `"@"`.
+//! To use more than one flag, pass an array (`impl = ["@", "#internal"]`) or
+//! repeat the argument.
+//!
+//! Flags are matched token by token, so `impl = "@i"` matches `(@i ...)` but
+//! not `(@impl ...)`.
+//!
+//! Flags in other positions are given as `impl(kind = "...")`, where `kind` is one of
+//! - `prefix`: the rule starts with the flag, the same as `impl = "..."`.
+//! - `suffix`: the rule ends with the flag, as in `($t:tt @end)`.
+//! - `wrapped`: the rule starts with a delimited group that starts with the
+//!   flag, as in `([internal] ...)`.
+//! - `literal`: the rule starts with a string literal with the flag's value,
+//!   as in `("__impl" ...)`.
+//!
+//! ```
+//! # use clean_macro_docs::clean_docs;
+//! #[clean_docs(impl(wrapped = "internal", literal = "__impl"))]
+//! #[macro_export]
+//! macro_rules! mac {
+//!     ([internal] $e:expr) => {
+//!         mac!("__impl" $e)
+//!     };
+//!     ("__impl" $e:expr) => {
+//!         format!("{}", $e)
+//!     };
+//!     ($e:expr) => {
+//!         mac!([internal] $e)
+//!     };
+//! }
+//! ```
 //!
 //! ```
 //! # use clean_macro_docs::clean_docs;
@@ -101,7 +342,16 @@
 //!
 //! ## `internal`
 //! A string representing the identifier to use for the internal version of your macro.
-//! By default `clean_docs` prepends `__` (two underscores) to the main macro's identifier.
+//! It can contain these placeholders:
+//!
+//! - `{name}`: the main macro's identifier, without any `r#` prefix.
+//! - `{crate}`: the name of the crate being compiled.
+//! - `{hash}`: a hash of the crate name and the macro's definition, which keeps the
+//!   name unique even when two modules export macros with the same name.
+//!
+//! If the result is a keyword, a raw identifier is used instead.
+//! By default `clean_docs` prepends `__` (two underscores) to the main macro's identifier,
+//! i.e. the template is `"__{name}"`.
 //!
 //! ```
 //! # use clean_macro_docs::clean_docs;
@@ -115,16 +365,407 @@
 //!         mac!(@impl $e)
 //!     };
 //! }
+//!
+//! #[clean_docs(internal = "__{crate}_{name}_{hash}")]
+//! #[macro_export]
+//! macro_rules! other_mac {
+//!     (@impl $e:expr) => {
+//!         format!("{}", $e)
+//!     };
+//!     ($e:expr) => {
+//!         other_mac!(@impl $e)
+//!     };
+//! }
 //! ```
 //!
+//! ## `internal_path`
+//! A string with the path of a module, relative to the crate root, such as
+//! `"macros::__private"`. Instead of being called as `$crate::__mac!`, the internal macro
+//! is re-exported under the main macro's name in a `#[doc(hidden)]` module with that
+//! path, and called as `$crate::macros::__private::mac!`. The internal macro is still
+//! exported from the crate root, as it must be, but with a hash appended to its name so
+//! that it can't clash with other macros.
+//!
+//! It can only be set for a module, whose macros all share a single hidden module,
+//! including the ones in nested modules. The hidden module is created in the module
+//! that sets `internal_path`, so the path must be that module's path followed by the
+//! name of the hidden module. A nested module can set a different `internal_path` to
+//! get its own. Setting it for a single macro or a [family](#macro-families) is an
+//! error, since two of them in the same module would create the same hidden module.
+//! Macros that aren't exported don't need one, and ignore `internal_path`.
+//!
+//! ```
+//! # use clean_macro_docs::clean_docs;
+//! #[clean_docs(internal_path = "macros::__private")]
+//! pub mod macros {
+//!     #[macro_export]
+//!     macro_rules! mac {
+//!         (@impl $e:expr) => {
+//!             format!("{}", $e)
+//!         };
+//!         ($e:expr) => {
+//!             mac!(@impl $e)
+//!         };
+//!     }
+//!
+//!     // Re-exported as `macros::__private::nested_mac`
+//!     pub mod nested {
+//!         #[macro_export]
+//!         macro_rules! nested_mac {
+//!             (@impl $e:expr) => {
+//!                 format!("{}", $e)
+//!             };
+//!             ($e:expr) => {
+//!                 nested_mac!(@impl $e)
+//!             };
+//!         }
+//!     }
+//! }
+//! ```
+//!
+//! ## `crate_path`
+//! A string with an absolute path, such as `"::foo::__macros"`, to use instead of `$crate`
+//! when calling the internal macro. This is useful when your macros are defined in one
+//! crate and re-exported from a facade crate, and downstream crates only depend on the
+//! facade. The facade must re-export the internal macro at that path.
+//! Macros that aren't exported ignore `crate_path`.
+//!
+//! ```ignore
+//! // In `foo-macros-impl`
+//! #[clean_docs(crate_path = "::foo::__macros")]
+//! #[macro_export]
+//! macro_rules! mac {
+//!     (@impl $e:expr) => {
+//!         format!("{}", $e)
+//!     };
+//!     ($e:expr) => {
+//!         mac!(@impl $e)
+//!     };
+//! }
+//!
+//! // In `foo`
+//! pub use foo_macros_impl::mac;
+//!
+//! #[doc(hidden)]
+//! pub mod __macros {
+//!     pub use foo_macros_impl::__mac;
+//! }
+//! ```
+//!
+//! ## `local`
+//! A string that tells `clean_docs` how the crate itself reaches the full version of an
+//! exported macro. Defaults to `"shadow"`.
+//!
+//! - `"shadow"`: emit the local copy described in [How does it work?](#how-does-it-work),
+//!   which shadows the exported macro in textual scope after its definition. Its calls
+//!   to itself through `$crate::` are made without the path, so that they stay on the
+//!   local copy.
+//! - `"reexport"`: also re-export the local copy with `pub(crate) use`, so that it can be
+//!   called by path, e.g. as `crate::module::mac!(@impl ...)`. This only works for macros
+//!   defined outside the crate root, where the re-export doesn't clash with the exported
+//!   macro.
+//! - `"none"`: don't emit a local copy. Only use this if the crate never calls the macro
+//!   itself.
+//!
+//! Macros that aren't exported don't need a local copy, and ignore `local`.
+//!
+//! ## `qualify_self`
+//! A boolean. When `true`, calls from the macro to its own public rules, like
+//! `mac!($t)`, are rewritten to `$crate::mac!($t)`, so that they also work for crates
+//! that call the macro by path without importing it. Calls that already use a path are
+//! left alone. Defaults to `true` for exported macros, and is ignored for macros that
+//! aren't exported.
+//!
+//! ```
+//! # use clean_macro_docs::clean_docs;
+//! #[clean_docs(qualify_self = false)]
+//! #[macro_export]
+//! macro_rules! mac {
+//!     (@impl $t:tt) => {
+//!         (mac!($t), mac!($t))
+//!     };
+//!     ([$t:tt]) => {
+//!         mac!(@impl $t)
+//!     };
+//!     ($t:tt) => {
+//!         $t
+//!     };
+//! }
+//! ```
+//!
+//! ## `qualify`
+//! An array of other exported macros, such as `qualify = [helper_a, helper_b]`. Calls to
+//! them from the macro's rules are rewritten to `$crate::helper_a!(...)`, so that they
+//! work for crates that don't import the helpers. Calls that already use a path are left
+//! alone. When `clean_docs` is applied to a module, every `#[macro_export]`ed macro in the
+//! module is qualified automatically, and `qualify` adds to them.
+//!
+//! ```
+//! # use clean_macro_docs::clean_docs;
+//! #[macro_export]
+//! macro_rules! helper {
+//!     ($e:expr) => {
+//!         format!("{}", $e)
+//!     };
+//! }
+//!
+//! #[clean_docs(qualify = [helper])]
+//! #[macro_export]
+//! macro_rules! mac {
+//!     (@impl $e:expr) => {
+//!         helper!($e)
+//!     };
+//!     ($e:expr) => {
+//!         mac!(@impl $e)
+//!     };
+//! }
+//! ```
+//!
+//! ## `calls`
+//! Other cleaned macros whose internal rules this macro calls, such as
+//! `calls(helper_a, helper_b = "__helper_b_impl")`. Calls like `helper_a!(@impl ...)`
+//! are rewritten to call the internal macro of `helper_a` instead, the same way as calls
+//! to this macro's own internal rules. Without this, they would call the cleaned
+//! `helper_a!`, which no longer has those rules.
+//!
+//! Calls are recognized with this macro's `impl` flags. The internal name of each macro
+//! is found with this macro's `internal` template, unless it is given. Templates with
+//! `{hash}` can't be used here, since the hash depends on the other macro's definition.
+//! The other macros are expected to use the same `internal_path`, `crate_path`, `seal`
+//! and `warn_direct`.
+//!
+//! ```
+//! # use clean_macro_docs::clean_docs;
+//! #[clean_docs]
+//! #[macro_export]
+//! macro_rules! helper {
+//!     (@impl $e:expr) => {
+//!         format!("{}", $e)
+//!     };
+//!     ($e:expr) => {
+//!         helper!(@impl $e)
+//!     };
+//! }
+//!
+//! #[clean_docs(calls(helper))]
+//! #[macro_export]
+//! macro_rules! mac {
+//!     (@impl $e:expr) => {
+//!         helper!(@impl $e)
+//!     };
+//!     ($e:expr) => {
+//!         mac!(@impl $e)
+//!     };
+//! }
+//! ```
+//!
+//! ## `local_inner_macros`
+//! A boolean. When `true`, the exported macro and its internal macro are emitted with
+//! `#[macro_export(local_inner_macros)]`, and internal calls are rewritten to
+//! `__mac!(...)` instead of `$crate::__mac!(...)`. This is for crates whose users still
+//! import macros with `#[macro_use] extern crate`, on toolchains that don't support
+//! `$crate::` in macro paths. Defaults to `false`, and is ignored for macros that aren't
+//! exported.
+//!
+//! `local_inner_macros` makes every macro called without a path resolve in the crate
+//! of the macro, so `format!(...)` would become `$crate::format!(...)`. A warning is
+//! emitted for each such call, unless the macro is this macro itself, one in `calls`, or
+//! one in `qualify`. Call other macros by path, like `::std::format!(...)`. Since calls
+//! can't name a module, `internal_path` and `crate_path` are ignored for the internal
+//! macro.
+//!
+//! ```
+//! # use clean_macro_docs::clean_docs;
+//! #[clean_docs(local_inner_macros)]
+//! #[macro_export]
+//! macro_rules! mac {
+//!     (@impl $e:expr) => {
+//!         ::std::format!("{}", $e)
+//!     };
+//!     ($e:expr) => {
+//!         mac!(@impl $e)
+//!     };
+//! }
+//! ```
+//!
+//! ## `split`
+//! A boolean. When `true`, the internal rules are also split into one internal macro for
+//! each sub-marker, the identifier right after an `impl` prefix, such as `parse` in
+//! `(@parse ...)`. Invocations like `mac!(@parse ...)` are rewritten to call
+//! `__mac_parse!`, which only has the `@parse` rules, so that a tt-muncher doesn't try
+//! every internal rule on every step. Defaults to `false`.
+//!
+//! A delimited group right after the identifier is part of the sub-marker, unless it
+//! has metavariables: the `(@step[0] ...)` and `(@step[1] ...)` rules go to
+//! `__mac_step_0` and `__mac_step_1`. Invocations whose group isn't known, like
+//! `mac!(@step[$n] ...)`, call `__mac_step!`, which has every `@step` rule.
+//!
+//! Rules without a sub-marker, like `(@ $step:ident ...)`, could match any invocation,
+//! so they are kept in every internal macro, as are rules like `(@step[$n:tt] ...)` in
+//! every `@step` macro. Invocations without a sub-marker, or with one that no rule has,
+//! still call `__mac!`, which keeps all of the internal rules. With `internal_path`,
+//! `__mac_parse` is re-exported as `mac_parse`. The internal macro of a
+//! [family](#macro-families) isn't split.
+//!
+//! ```
+//! # use clean_macro_docs::clean_docs;
+//! #[clean_docs(split)]
+//! #[macro_export]
+//! macro_rules! mac {
+//!     (@parse [$($acc:tt)*]) => {
+//!         mac!(@emit $($acc)*)
+//!     };
+//!     (@parse [$($acc:tt)*] $x:literal $($rest:tt)*) => {
+//!         mac!(@parse [$($acc)* $x] $($rest)*)
+//!     };
+//!     (@emit $($x:literal)*) => {
+//!         [$($x),*]
+//!     };
+//!     ($($x:literal)*) => {
+//!         mac!(@parse [] $($x)*)
+//!     };
+//! }
+//! ```
+//!
+//! ## `seal`
+//! A boolean. When `true`, every internal rule starts with a token that only this crate
+//! knows, and the rewritten invocations of internal rules pass it, such as
+//! `$crate::__mac!(__sealed_9f0c... @impl $e)`. Other crates can then only reach the
+//! internal rules through the public ones, and get a "no rules expected" error when
+//! they call `__mac!(@impl ...)` directly. Defaults to `false`.
+//!
+//! The token is a hash of the crate's name and version, so it changes with every
+//! release. All of the crate's sealed macros share it. The local copy isn't sealed.
+//!
+//! ```
+//! # use clean_macro_docs::clean_docs;
+//! #[clean_docs(seal)]
+//! #[macro_export]
+//! macro_rules! mac {
+//!     (@impl $e:expr) => {
+//!         format!("{}", $e)
+//!     };
+//!     ($e:expr) => {
+//!         mac!(@impl $e)
+//!     };
+//! }
+//! ```
+//!
+//! ## `warn_direct`
+//! A boolean. A softer [`seal`](#seal): the rewritten invocations of internal rules pass
+//! the same token, but the internal macro still accepts invocations without it. Those
+//! only come from direct calls like `__mac!(@impl ...)`, and are sent to a deprecated
+//! copy of the internal macro, `__mac_direct`. Callers get a deprecation warning saying
+//! that the rules are internal to `mac!`, but their code keeps compiling. A rewritten
+//! invocation that matches no internal rule, like a failed tt-muncher step, is an error
+//! naming `mac!` rather than a direct call. Defaults to `false`, and is ignored for
+//! macros that aren't exported, or that are sealed.
+//!
+//! ```
+//! # use clean_macro_docs::clean_docs;
+//! #[clean_docs(warn_direct)]
+//! #[macro_export]
+//! macro_rules! mac {
+//!     (@impl $e:expr) => {
+//!         format!("{}", $e)
+//!     };
+//!     ($e:expr) => {
+//!         mac!(@impl $e)
+//!     };
+//! }
+//! ```
+//!
+//! ## `collision`
+//! A string that tells `clean_docs` what to do about the name of the internal macro
+//! being taken by another `clean_docs` macro in the same crate, e.g. when two modules
+//! export macros with the same name. Defaults to `"error"`.
+//!
+//! - `"error"`: the compiler reports the name as defined multiple times, pointing at
+//!   both macros.
+//! - `"rename"`: append a hash of the macro's definition to the internal macro's name,
+//!   so that it can't be taken.
+//!
+//! For each name that an exported macro adds to the crate root, `clean_docs` emits a
+//! hidden `#[macro_export]` guard macro, named like `__clean_macro_docs_name___mac`, with
+//! the macro's `#[cfg]` attributes. Names are checked in every build, not only when
+//! generating documentation, unless the [`mode`](#mode) is `"never"`. Alternative
+//! definitions of a macro under different `#[cfg]`s don't collide. Collisions with
+//! macros that don't use `clean_docs` are still reported by the compiler when generating
+//! documentation.
+//!
+//! ```
+//! # use clean_macro_docs::clean_docs;
+//! #[clean_docs(internal = "__shared", collision = "rename")]
+//! #[macro_export]
+//! macro_rules! mac {
+//!     (@impl $e:expr) => {
+//!         format!("{}", $e)
+//!     };
+//!     ($e:expr) => {
+//!         mac!(@impl $e)
+//!     };
+//! }
+//! ```
+//!
+//! ## `mode`
+//! A string that tells `clean_docs` when it should transform the macro. Defaults to `"doc"`.
+//!
+//! - `"doc"`: only when `rustdoc` sets the `doc` environment variable.
+//! - `"always"`: even when not building documentation. This is mainly used
+//!   for testing purposes.
+//! - `"never"`: never. The macro is emitted untouched.
+//! - `"cfg"`: always emit both versions, and let the compiler pick one
+//!   (see [`cfg`](#cfg)).
+//!
+//! ```
+//! # use clean_macro_docs::clean_docs;
+//! #[clean_docs(mode = "always")]
+//! #[macro_export]
+//! macro_rules! mac {
+//!     (@impl $e:expr) => {
+//!         format!("{}", $e)
+//!     };
+//!     ($e:expr) => {
+//!         mac!(@impl $e)
+//!     };
+//! }
+//! ```
+//!
+//! ### Overriding the mode
+//! The `mode` of every `clean_docs` macro in a crate can be overridden by
+//! setting the `CLEAN_MACRO_DOCS_MODE` environment variable to one of the
+//! values above, e.g. to force the transformation in CI:
+//!
+//! ```text
+//! CLEAN_MACRO_DOCS_MODE=always cargo test
+//! ```
+//!
+//! Cargo doesn't track this variable, so you may need to `cargo clean` after
+//! changing it.
+//!
+//! The `force-always`, `force-cfg` and `force-never` Cargo features of
+//! `clean-macro-docs` do the same. The environment variable takes precedence
+//! over the features, and `force-never` takes precedence over `force-cfg`,
+//! which takes precedence over `force-always`.
+//!
 //! ## `always`
-//! A boolean that tells `clean_docs` whether it should transform the macro
-//! even when not building documentation. This is mainly used for testing
-//! purposes. Defaults to `false`.
+//! A boolean. `always = true` (or just `always`) is the same as
+//! `mode = "always"`, and `always = false` is the same as `mode = "doc"`. Kept
+//! for backwards compatibility.
+//!
+//! ## `cfg`
+//! A string containing the `cfg` predicate used by `mode = "cfg"`, such as
+//! `"docsrs"`. Defaults to `"doc"`. Setting `cfg` implies `mode = "cfg"`,
+//! unless another `mode` is given.
+//!
+//! With `mode = "cfg"`, `clean_docs` always emits both versions of your macro:
+//! the transformed macros under `#[cfg(<predicate>)]`, and the untouched macro
+//! under `#[cfg(not(<predicate>))]`. The compiler then picks the right one, so
+//! you don't have to rely on `rustdoc` setting the `doc` environment variable.
 //!
 //! ```
 //! # use clean_macro_docs::clean_docs;
-//! #[clean_docs(always = true)]
+//! #[clean_docs(cfg = "any(doc, docsrs)")]
 //! #[macro_export]
 //! macro_rules! mac {
 //!     (@impl $e:expr) => {
@@ -139,107 +780,377 @@
 extern crate proc_macro;
 extern crate proc_macro2;
 
-use proc_macro2::{Punct, Spacing, TokenStream, TokenTree};
-use quote::{format_ident, quote, quote_spanned};
-use std::str::FromStr;
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote, quote_spanned, ToTokens};
+use syn::parse::{Parse, ParseStream};
 use syn::punctuated::Punctuated;
-use syn::spanned::Spanned;
-use syn::{parse_macro_input, AttributeArgs, Ident, Lit, Meta, NestedMeta, Token};
+use syn::{parse_macro_input, parse_quote, Attribute, Ident, ItemMod, LitStr, Token, Visibility};
 
+mod args;
+#[cfg(feature = "decl-macro")]
+mod decl_macro;
+mod family;
+mod local;
 mod macro_rules;
+mod manifest;
+mod marker;
+mod mode;
+mod module;
+mod naming;
+mod reexport;
 mod replace_macro_invocs;
+mod split;
+mod warning;
 
+use args::{Args, RawArg};
+#[cfg(feature = "decl-macro")]
+use decl_macro::{check_decl_macro_args, clean_decl_macro, DeclMacro};
+use family::{clean_family, Family, Shared};
+use local::Local;
 use macro_rules::*;
-use replace_macro_invocs::replace_macro_invocs;
+use manifest::manifest_defaults;
+use marker::{default_markers, Classifier};
+use mode::Mode;
+use module::clean_mod;
+use naming::{expand_template, name_guard, rename, seal_ident, Collision, DEFAULT_TEMPLATE};
+use reexport::{check_internal_path, Reexport};
+use replace_macro_invocs::{
+    bare_macro_invocs, macro_path, replace_macro_invocs, unqualify_macro_invocs, Call, Rewrites,
+};
+use split::{sub_macros, sub_rules};
+use warning::warning;
 
 #[proc_macro_attribute]
 pub fn clean_docs(
     args: proc_macro::TokenStream,
     item: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-    let args = parse_macro_input!(args as AttributeArgs);
-    let mac_rules = parse_macro_input!(item as MacroRules);
-    clean_docs_impl(args, mac_rules).into()
+    let args = parse_macro_input!(args with Punctuated::<RawArg, Token![,]>::parse_terminated);
+    let item = parse_macro_input!(item as CleanDocsItem);
+    clean_docs_impl(args.into_iter().collect(), item).into()
+}
+
+#[proc_macro]
+pub fn clean_docs_family(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let family = parse_macro_input!(input as Family);
+    clean_docs_family_impl(family).into()
+}
+
+// An item that `clean_docs` can be applied to.
+enum CleanDocsItem {
+    MacroRules(MacroRules),
+    Mod(ItemMod),
+    #[cfg(feature = "decl-macro")]
+    DeclMacro(DeclMacro),
 }
 
-fn clean_docs_impl(args: AttributeArgs, mut mac_rules: MacroRules) -> TokenStream {
-    let mut run_always = false;
-    let mut priv_marker: Option<TokenStream> = None;
-    let mut priv_ident: Option<Ident> = None;
-
-    for arg in args {
-        if let NestedMeta::Meta(Meta::NameValue(arg)) = arg {
-            match (
-                arg.path
-                    .get_ident()
-                    .map(Ident::to_string)
-                    .as_ref()
-                    .map(String::as_str),
-                arg.lit,
-            ) {
-                (Some("always"), Lit::Bool(val)) => run_always = val.value,
-                (Some("impl"), Lit::Str(val)) => {
-                    priv_marker = Some({
-                        if let Ok(priv_marker) = TokenStream::from_str(&val.value()) {
-                            priv_marker
-                        } else {
-                            return quote_spanned! {
-                                arg.path.span()=> compile_error!("invalid tokens");
-                            }
-                        }
-                    })
-                }
-                (Some("internal"), Lit::Str(val)) => {
-                    priv_ident = Some({
-                        if let Ok(priv_ident) = val.parse() {
-                            priv_ident
-                        } else {
-                            return quote_spanned! {
-                                arg.path.span()=> compile_error!("expected identifier");
-                            }
-                        }
-                    })
-                }
-                _ => {
-                    let arg_str = quote!(arg).to_string();
-                    return quote_spanned! {
-                        arg.path.span()=> compile_error!(concat!("invalid argument: ", #arg_str));
-                    };
-                }
-            };
+impl Parse for CleanDocsItem {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let fork = input.fork();
+        fork.call(Attribute::parse_outer)?;
+        fork.parse::<Visibility>()?;
+        if fork.peek(Token![mod]) {
+            input.parse().map(CleanDocsItem::Mod)
+        } else if fork.peek(Token![macro]) {
+            #[cfg(feature = "decl-macro")]
+            return input.parse().map(CleanDocsItem::DeclMacro);
+            #[cfg(not(feature = "decl-macro"))]
+            return Err(fork.error("`macro` items require the `decl-macro` feature"));
+        } else {
+            input.parse().map(CleanDocsItem::MacroRules)
         }
     }
+}
 
-    // Only run when generating docs, or if always is true
-    if !run_always && std::env::var("doc").is_err() {
-        return quote! {
-            #mac_rules
-        };
+fn clean_docs_impl(args: Vec<RawArg>, item: CleanDocsItem) -> TokenStream {
+    let defaults = match manifest_defaults() {
+        Ok(defaults) => defaults,
+        Err(err) => {
+            return quote! {
+                compile_error!(#err);
+            }
+        }
+    };
+    #[cfg(feature = "decl-macro")]
+    {
+        if let CleanDocsItem::DeclMacro(_) = &item {
+            if let Err(err) = check_decl_macro_args(&args) {
+                return err.to_compile_error();
+            }
+        }
+    }
+    let args = match Args::parse(args) {
+        Ok(args) => args.with_defaults(&defaults),
+        Err(err) => return err.to_compile_error(),
+    };
+
+    match item {
+        CleanDocsItem::MacroRules(mac_rules) => match check_internal_path(&args, &defaults) {
+            Ok(()) => clean_macro_rules(args, mac_rules, &mut Vec::new(), None),
+            Err(err) => err.to_compile_error(),
+        },
+        CleanDocsItem::Mod(item_mod) => clean_mod(args, item_mod),
+        #[cfg(feature = "decl-macro")]
+        CleanDocsItem::DeclMacro(decl_macro) => clean_decl_macro(args, decl_macro),
     }
+}
+
+fn clean_docs_family_impl(family: Family) -> TokenStream {
+    match manifest_defaults() {
+        Ok(defaults) => clean_family(defaults, family),
+        Err(err) => quote! {
+            compile_error!(#err);
+        },
+    }
+}
+
+// Clean a single macro. Re-exports of the internal macro for `internal_path`
+// are pushed to `reexports`, to be emitted in the hidden module of the module
+// that sets it.
+// The macros of a family are cleaned one by one, with the internal macro they
+// share as `shared`.
+fn clean_macro_rules(
+    args: Args,
+    mut mac_rules: MacroRules,
+    reexports: &mut Vec<Reexport>,
+    shared: Option<Shared>,
+) -> TokenStream {
+    let Args {
+        mode,
+        cfg,
+        priv_markers,
+        priv_ident,
+        internal_path,
+        crate_path,
+        local,
+        qualify_self,
+        qualify,
+        calls,
+        local_inner_macros,
+        split,
+        seal,
+        warn_direct,
+        collision,
+    } = args;
+
+    // Only run when generating docs, unless mode says otherwise
+    let mode = match Mode::resolve(mode, &cfg) {
+        Ok(mode) => mode,
+        Err(err) => {
+            return quote! {
+                compile_error!(#err);
+            }
+        }
+    };
+    let exported = mac_rules
+        .attrs
+        .iter()
+        .any(|attr| attr.path.is_ident("macro_export"));
+    // Macros that aren't exported are left as written, see "Macros that
+    // aren't exported" in the crate docs
+    let run = mode.runs() && exported;
 
     // Clone item, to be reimitted unmodified without #[macro_export]
     let mut original = mac_rules.clone();
+    // Clone item, to be reemitted untouched when `cfg` is set
+    let untouched = mac_rules.clone();
 
     let pub_ident = &mac_rules.ident;
 
     // Default values
-    let priv_marker = priv_marker
-        .unwrap_or_else(|| TokenStream::from(TokenTree::Punct(Punct::new('@', Spacing::Joint))));
-    let priv_ident = priv_ident
-        .unwrap_or_else(|| format_ident!("__{}", pub_ident));
+    let priv_markers = priv_markers.unwrap_or_else(default_markers);
+    let (template, template_span) = match &priv_ident {
+        Some(priv_ident) => (priv_ident.value(), priv_ident.span()),
+        None => (DEFAULT_TEMPLATE.to_string(), Span::call_site()),
+    };
+    let definition = untouched.to_token_stream();
+    let mut priv_ident = match (&shared, expand_template(&template, pub_ident, &definition)) {
+        (Some(shared), _) => shared.ident.clone(),
+        (None, Ok(priv_ident)) => priv_ident,
+        (None, Err(err)) => {
+            return quote_spanned! {
+                template_span=> compile_error!(#err);
+            }
+        }
+    };
+
+    // With `local_inner_macros`, calls without a path resolve at the crate
+    // root, so internal macros are called by name, and nothing is qualified
+    let local_inner_macros = exported && local_inner_macros.unwrap_or(false);
+    let qualify = qualify.unwrap_or_default();
+
+    // With `internal_path`, the internal macro is only called through the
+    // hidden module, so its name at the crate root can be made unique. The
+    // other macros of a family couldn't follow a new name.
+    let internal_path = internal_path.filter(|_| exported && !local_inner_macros);
+    if (internal_path.is_some() || collision == Some(Collision::Rename)) && shared.is_none() {
+        priv_ident = rename(&priv_ident, &definition);
+    }
+
+    // Only the first macro of a family emits the internal macro, and the
+    // `#[internal]` macro as written
+    let (emit_internal, shared_untouched) = match &shared {
+        Some(shared) => (shared.untouched.is_some(), shared.untouched.clone()),
+        None => (true, None),
+    };
+
+    // How the internal macro `priv_ident` of the macro `pub_ident` is called
+    let priv_path = |pub_ident: &Ident, priv_ident: &Ident| match &internal_path {
+        Some(path) => Some(macro_path(
+            crate_path.as_ref(),
+            path.segments.iter().map(|segment| &segment.ident),
+            pub_ident,
+        )),
+        None if exported && !local_inner_macros => {
+            Some(macro_path(crate_path.as_ref(), None, priv_ident))
+        }
+        None => None,
+    };
+
+    let other_calls = match other_calls(calls, &template, priv_path) {
+        Ok(other_calls) => other_calls,
+        Err(err) => return err,
+    };
+
+    let classifier = Classifier::new(priv_markers, &mac_rules.rules);
+
+    // With `split`, internal rules are also sent to an internal macro for
+    // each sub-marker. The internal macro of a family isn't split.
+    let split = if split.unwrap_or(false) && shared.is_none() {
+        sub_macros(&classifier, pub_ident, &priv_ident, &mac_rules.rules, priv_path)
+    } else {
+        Vec::new()
+    };
+
+    // With `warn_direct`, direct calls to the internal macro go through a
+    // deprecated copy of it. Sealed rules can't be called directly at all.
+    let seal = seal.unwrap_or(false);
+    let warn_direct = exported && !seal && warn_direct.unwrap_or(false);
+    let direct_ident = if warn_direct && emit_internal {
+        Some(format_ident!("{}_direct", priv_ident))
+    } else {
+        None
+    };
+
+    // Claim the names this macro adds to the crate root, even when not
+    // running, so that collisions don't only show up in doc builds. Macros
+    // that aren't exported don't add any, and with `mode = "never"`, nothing
+    // is added to the macro.
+    let guards: TokenStream = if exported && mode != Mode::Never {
+        let (internal, public): (Vec<_>, Vec<_>) = mac_rules
+            .rules
+            .iter()
+            .partition(|rule| classifier.is_internal_rule(rule));
+        let has_internal = emit_internal && !internal.is_empty() && !public.is_empty();
+        let cfgs: Vec<&Attribute> = mac_rules
+            .attrs
+            .iter()
+            .filter(|attr| attr.path.is_ident("cfg"))
+            .collect();
+        Some(pub_ident)
+            .into_iter()
+            .chain(has_internal.then_some(&priv_ident))
+            .chain(split.iter().map(|sub_macro| &sub_macro.priv_ident))
+            .chain(&direct_ident)
+            .map(|name| name_guard(name, pub_ident, &cfgs))
+            .collect()
+    } else {
+        TokenStream::new()
+    };
+
+    // Warn about the other calls that `local_inner_macros` would send to the
+    // crate root, even when not running. The rules of the `#[internal]` macro
+    // of a family are only checked once.
+    let warnings = if local_inner_macros {
+        let known: Vec<&Ident> = [pub_ident, &priv_ident]
+            .iter()
+            .copied()
+            .chain(&qualify)
+            .chain(other_calls.iter().flat_map(|call| vec![&call.pub_ident, &call.priv_ident]))
+            .collect();
+        mac_rules
+            .rules
+            .iter()
+            .filter(|rule| emit_internal || !classifier.is_internal_rule(rule))
+            .flat_map(|rule| bare_macro_invocs(rule.body.clone()))
+            .filter(|ident| !known.contains(&ident))
+            .map(|ident| {
+                warning(
+                    ident.span(),
+                    &format!(
+                        "with `local_inner_macros`, `{0}!` is called as `$crate::{0}!`; call it \
+                        by path, or add it to `qualify` if this crate exports it",
+                        ident
+                    ),
+                )
+            })
+            .collect()
+    } else {
+        TokenStream::new()
+    };
+
+    if !run {
+        return quote! {
+            #mac_rules
+            #shared_untouched
+            #guards
+            #warnings
+        };
+    }
 
     let mut pub_rules = Punctuated::<MacroRulesRule, Token![;]>::new();
     let mut priv_rules = Punctuated::<MacroRulesRule, Token![;]>::new();
 
+    let seal = if seal || warn_direct {
+        Some(seal_ident())
+    } else {
+        None
+    };
+
+    let rewrites = Rewrites {
+        pub_ident,
+        priv_ident: &priv_ident,
+        priv_path: priv_path(pub_ident, &priv_ident),
+        // Calls to public rules and to other exported macros are qualified
+        // too, so that they work for crates that call the macro by path
+        // without importing anything else
+        self_path: if !local_inner_macros && qualify_self.unwrap_or(true) {
+            Some(macro_path(None, None, pub_ident))
+        } else {
+            None
+        },
+        qualify: if !local_inner_macros {
+            qualify
+                .into_iter()
+                .map(|ident| {
+                    let path = macro_path(None, None, &ident);
+                    (ident, path)
+                })
+                .collect()
+        } else {
+            Vec::new()
+        },
+        calls: other_calls,
+        split: &split,
+        seal: seal.clone(),
+        classifier: &classifier,
+    };
+
     for mut rule in mac_rules.rules {
-        rule.body = replace_macro_invocs(rule.body, pub_ident, &priv_ident, &priv_marker);
-        if rule.rule.to_string().starts_with(&priv_marker.to_string()) {
+        rule.body = replace_macro_invocs(rule.body, &rewrites);
+        if classifier.is_internal_rule(&rule) {
             priv_rules.push(rule);
         } else {
             pub_rules.push(rule);
         }
     }
 
+    let local_idents: Vec<Ident> = Some(pub_ident)
+        .into_iter()
+        .chain(rewrites.calls.iter().map(|call| &call.pub_ident))
+        .cloned()
+        .collect();
+
     if pub_rules.is_empty() {
         return quote! {
             compile_error!("no public rules");
@@ -249,6 +1160,8 @@ fn clean_docs_impl(args: AttributeArgs, mut mac_rules: MacroRules) -> TokenStrea
     if priv_rules.is_empty() {
         return quote! {
             #original
+            #guards
+            #warnings
         };
     }
 
@@ -259,6 +1172,16 @@ fn clean_docs_impl(args: AttributeArgs, mut mac_rules: MacroRules) -> TokenStrea
 
     mac_rules.rules = pub_rules;
 
+    // The internal macro is exported the same way, so that its calls to the
+    // public macro resolve too
+    if local_inner_macros {
+        for attr in mac_rules.attrs.iter_mut() {
+            if attr.path.is_ident("macro_export") {
+                *attr = parse_quote!(#[macro_export(local_inner_macros)]);
+            }
+        }
+    }
+
     let mut priv_mac_rules = MacroRules {
         ident: priv_ident,
         rules: priv_rules,
@@ -268,30 +1191,218 @@ fn clean_docs_impl(args: AttributeArgs, mut mac_rules: MacroRules) -> TokenStrea
     // Remove doc comments (and other doc attrs) from private version
     priv_mac_rules.attrs.retain(|attr| {
         if let Some(ident) = attr.path.get_ident() {
-            ident.to_string() != "doc"
+            ident != "doc"
         } else {
             true
         }
     });
 
+    // The internal macro of each sub-marker has the rules that its
+    // invocations could match
+    let mut sub_mac_rules: Vec<MacroRules> = split
+        .iter()
+        .map(|sub_macro| MacroRules {
+            ident: sub_macro.priv_ident.clone(),
+            rules: sub_rules(&classifier, &priv_mac_rules.rules, &sub_macro.sub),
+            ..priv_mac_rules.clone()
+        })
+        .collect();
+
+    // The deprecated copy has the internal rules without the seal, and warns
+    // whoever calls it
+    let direct_mac_rules = direct_ident.as_ref().map(|direct_ident| {
+        let note = format!(
+            "internal to `{0}!`, and may change in any release; use `{0}!` instead",
+            pub_ident
+        );
+        let mut direct_mac_rules = MacroRules {
+            ident: direct_ident.clone(),
+            ..priv_mac_rules.clone()
+        };
+        direct_mac_rules
+            .attrs
+            .push(parse_quote!(#[deprecated(note = #note)]));
+        direct_mac_rules
+    });
+
+    // Sealed internal rules only match invocations that start with the seal,
+    // which only the rewritten invocations have
+    if let Some(seal) = &seal {
+        for internal_mac_rules in Some(&mut priv_mac_rules).into_iter().chain(&mut sub_mac_rules) {
+            for rule in internal_mac_rules.rules.iter_mut() {
+                let rule_tokens = &rule.rule;
+                rule.rule = quote!(#seal #rule_tokens);
+            }
+        }
+    }
+
+    // Any other invocation of the internal macros is a direct call, except
+    // for the sealed ones, which come from this crate's macros and match no
+    // internal rule
+    if let (Some(direct_ident), Some(seal)) = (&direct_ident, &seal) {
+        let direct_path = priv_path(&format_ident!("{}_direct", pub_ident), direct_ident)
+            .unwrap_or_else(|| direct_ident.to_token_stream());
+        let unmatched_msg = format!("no internal rule of `{}!` expected `", pub_ident);
+        let unmatched: MacroRulesRule = parse_quote! {
+            (#seal $($tokens:tt)*) => {
+                compile_error!(concat!(#unmatched_msg, stringify!($($tokens)*), "`"))
+            }
+        };
+        let forward: MacroRulesRule = parse_quote! {
+            ($($tokens:tt)*) => {
+                #direct_path!($($tokens)*)
+            }
+        };
+        for internal_mac_rules in Some(&mut priv_mac_rules).into_iter().chain(&mut sub_mac_rules) {
+            let trailing_punct = internal_mac_rules.rules.trailing_punct();
+            internal_mac_rules.rules.push(unmatched.clone());
+            internal_mac_rules.rules.push(forward.clone());
+            if trailing_punct {
+                internal_mac_rules.rules.push_punct(Default::default());
+            }
+        }
+    }
+
     // Remove #[macro_export] and doc comments (and other doc attrs) from crate-internal version
     original.attrs.retain(|attr| {
         if let Some(ident) = attr.path.get_ident() {
-            ident.to_string() != "macro_export" && ident.to_string() != "doc"
+            ident != "macro_export" && ident != "doc"
         } else {
             true
         }
     });
 
-    let gen = quote! {
+    // The crate-internal version calls itself, and the other macros whose
+    // internal rules it calls, without `$crate::`, so that their
+    // crate-internal versions are used
+    let local_idents: Vec<&Ident> = local_idents.iter().collect();
+    for rule in original.rules.iter_mut() {
+        rule.body = unqualify_macro_invocs(rule.body.clone(), &local_idents);
+    }
+
+    // With `mode = "cfg"`, every item is gated, and the untouched macro is
+    // used otherwise
+    let untouched: Vec<TokenStream> = Some(untouched.to_token_stream())
+        .into_iter()
+        .chain(shared_untouched)
+        .collect();
+    let (gate, fallback) = mode.gate(cfg, &untouched);
+
+    if internal_path.is_some() {
+        let priv_name = &priv_mac_rules.ident;
+        reexports.push(Reexport {
+            path: Vec::new(),
+            attrs: gate.clone(),
+            name: priv_name.clone(),
+            alias: pub_ident.clone(),
+        });
+        for sub_macro in &split {
+            reexports.push(Reexport {
+                path: Vec::new(),
+                attrs: gate.clone(),
+                name: sub_macro.priv_ident.clone(),
+                alias: sub_macro.pub_ident.clone(),
+            });
+        }
+        if let Some(direct_ident) = &direct_ident {
+            reexports.push(Reexport {
+                path: Vec::new(),
+                attrs: quote! {
+                    #gate
+                    #[allow(deprecated)]
+                },
+                name: direct_ident.clone(),
+                alias: format_ident!("{}_direct", pub_ident),
+            });
+        }
+    }
+    // The local copy lets the crate itself keep calling the internal
+    // rules, which the exported macro can't do by absolute path
+    let local = match local.unwrap_or(Local::Shadow) {
+        Local::Shadow => quote! {
+            #gate
+            #[allow(unused_macros)]
+            #original
+        },
+        Local::Reexport => quote! {
+            #gate
+            #[allow(unused_macros)]
+            #original
+            #gate
+            #[allow(unused_imports)]
+            pub(crate) use #pub_ident;
+        },
+        Local::None => quote!(),
+    };
+    let direct_mac_rules = direct_mac_rules.iter();
+    let priv_mac_rules = if emit_internal {
+        quote! {
+            #gate
+            #[doc(hidden)]
+            #priv_mac_rules
+            #(
+                #gate
+                #[doc(hidden)]
+                #sub_mac_rules
+            )*
+            #(
+                #gate
+                #[doc(hidden)]
+                #direct_mac_rules
+            )*
+        }
+    } else {
+        quote!()
+    };
+    quote! {
+        #gate
         #mac_rules
-        #[doc(hidden)]
         #priv_mac_rules
 
-        #[allow(unused_macros)]
-        #original
-    };
-    gen.into()
+        #local
+
+        #fallback
+        #guards
+        #warnings
+    }
+}
+
+// The internal macros of the macros in `calls`, found with `template` unless
+// their names are given, and called through `priv_path`. A hash can't be
+// computed without the other macros' definitions.
+fn other_calls(
+    calls: Option<Vec<(Ident, Option<LitStr>)>>,
+    template: &str,
+    priv_path: impl Fn(&Ident, &Ident) -> Option<TokenStream>,
+) -> Result<Vec<Call>, TokenStream> {
+    let mut other_calls = Vec::new();
+    for (call_ident, call_template) in calls.unwrap_or_default() {
+        let (call_template, call_span) = match &call_template {
+            Some(call_template) => (call_template.value(), call_template.span()),
+            None => (template.to_string(), call_ident.span()),
+        };
+        let call_priv_ident = if call_template.contains("{hash}") {
+            Err(format!(
+                "the internal name of `{0}` can't contain `{{hash}}`, give it as `calls({0} = \"...\")`",
+                call_ident
+            ))
+        } else {
+            expand_template(&call_template, &call_ident, &TokenStream::new())
+        };
+        match call_priv_ident {
+            Ok(call_priv_ident) => other_calls.push(Call {
+                priv_path: priv_path(&call_ident, &call_priv_ident),
+                pub_ident: call_ident,
+                priv_ident: call_priv_ident,
+            }),
+            Err(err) => {
+                return Err(quote_spanned! {
+                    call_span=> compile_error!(#err);
+                })
+            }
+        }
+    }
+    Ok(other_calls)
 }
 
 #[cfg(test)]
@@ -0,0 +1,29 @@
+use std::str::FromStr;
+
+/// How the crate itself reaches the full version of an exported macro.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Local {
+    /// Shadow the exported macro with a local copy, in textual scope.
+    Shadow,
+    /// Like `Shadow`, and also re-export the copy with `pub(crate) use`, so
+    /// that it can be called by path.
+    Reexport,
+    /// Don't emit a local copy.
+    None,
+}
+
+impl FromStr for Local {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "shadow" => Ok(Local::Shadow),
+            "reexport" => Ok(Local::Reexport),
+            "none" => Ok(Local::None),
+            _ => Err(format!(
+                "invalid local strategy `{}`, expected one of `shadow`, `reexport` or `none`",
+                s
+            )),
+        }
+    }
+}
@@ -1,29 +1,20 @@
 extern crate proc_macro2;
 
-use proc_macro2::{Delimiter, TokenStream, TokenTree};
+use proc_macro2::{Delimiter, Group, Span, TokenStream, TokenTree};
 use quote::{ToTokens, TokenStreamExt};
 use syn::parse::{Parse, ParseStream, Parser};
 use syn::punctuated::Punctuated;
 use syn::token::{Brace, Bracket, Paren};
 use syn::{Attribute, Error, Ident, MacroDelimiter, Path, Result, Token};
 
+use crate::marker::literal_prefix;
+
 fn parse_macro_delimeter(input: ParseStream) -> Result<(MacroDelimiter, TokenStream)> {
     input.step(|cursor| {
         if let Some((TokenTree::Group(g), new_cursor)) = cursor.token_tree() {
-            match g.delimiter() {
-                Delimiter::Parenthesis => Ok((
-                    (MacroDelimiter::Paren(Paren(g.span())), g.stream()),
-                    new_cursor,
-                )),
-                Delimiter::Brace => Ok((
-                    (MacroDelimiter::Brace(Brace(g.span())), g.stream()),
-                    new_cursor,
-                )),
-                Delimiter::Bracket => Ok((
-                    (MacroDelimiter::Bracket(Bracket(g.span())), g.stream()),
-                    new_cursor,
-                )),
-                Delimiter::None => Err(Error::new(g.span(), "expected delimited group")),
+            match macro_delimiter(&g) {
+                Some(delimited) => Ok((delimited, new_cursor)),
+                None => Err(Error::new(g.span(), "expected delimited group")),
             }
         } else {
             Err(Error::new(cursor.span(), "expected delimited group"))
@@ -31,6 +22,24 @@ fn parse_macro_delimeter(input: ParseStream) -> Result<(MacroDelimiter, TokenStr
     })
 }
 
+// The delimiter and contents of a group. A group without delimiters, like the
+// ones around the fragments substituted by another macro (`$body:block`), is
+// looked through if it holds a single delimited group.
+fn macro_delimiter(g: &Group) -> Option<(MacroDelimiter, TokenStream)> {
+    match g.delimiter() {
+        Delimiter::Parenthesis => Some((MacroDelimiter::Paren(Paren(g.span())), g.stream())),
+        Delimiter::Brace => Some((MacroDelimiter::Brace(Brace(g.span())), g.stream())),
+        Delimiter::Bracket => Some((MacroDelimiter::Bracket(Bracket(g.span())), g.stream())),
+        Delimiter::None => {
+            let mut inner = g.stream().into_iter();
+            match (inner.next(), inner.next()) {
+                (Some(TokenTree::Group(inner)), None) => macro_delimiter(&inner),
+                _ => None,
+            }
+        }
+    }
+}
+
 macro_rules! macro_delimited {
     ($content:ident in $input:expr) => {{
         let (delim, content) = parse_macro_delimeter($input)?;
@@ -39,7 +48,15 @@ macro_rules! macro_delimited {
     }};
 }
 
-fn macro_delimiter_surround<F>(delim: &MacroDelimiter, tokens: &mut TokenStream, f: F)
+fn delimiter_span(delim: &MacroDelimiter) -> Span {
+    match delim {
+        MacroDelimiter::Paren(paren) => paren.span,
+        MacroDelimiter::Brace(brace) => brace.span,
+        MacroDelimiter::Bracket(bracket) => bracket.span,
+    }
+}
+
+pub fn macro_delimiter_surround<F>(delim: &MacroDelimiter, tokens: &mut TokenStream, f: F)
 where
     F: FnOnce(&mut TokenStream),
 {
@@ -50,8 +67,38 @@ where
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum RuleAnnotation {
+    Internal,
+    Public,
+}
+
+// Parse an optional `#[internal]` or `#[public]` before a rule.
+fn parse_rule_annotation(input: ParseStream) -> Result<Option<RuleAnnotation>> {
+    if !input.peek(Token![#]) {
+        return Ok(None);
+    }
+
+    let attrs = input.call(Attribute::parse_outer)?;
+    match attrs.as_slice() {
+        [attr] if attr.tokens.is_empty() && attr.path.is_ident("internal") => {
+            Ok(Some(RuleAnnotation::Internal))
+        }
+        [attr] if attr.tokens.is_empty() && attr.path.is_ident("public") => {
+            Ok(Some(RuleAnnotation::Public))
+        }
+        _ => Err(Error::new(
+            attrs[0].pound_token.span,
+            "expected `#[internal]` or `#[public]`",
+        )),
+    }
+}
+
+// The annotation isn't emitted by `to_tokens`, since `macro_rules!` doesn't
+// allow attributes on rules.
 #[derive(Clone)]
 pub struct MacroRulesRule {
+    pub annotation: Option<RuleAnnotation>,
     pub rule_delimiter: MacroDelimiter,
     pub rule: TokenStream,
     pub fat_arrow: Token![=>],
@@ -63,8 +110,19 @@ impl Parse for MacroRulesRule {
     fn parse(input: ParseStream) -> Result<Self> {
         let rule;
         let body;
+        let annotation = parse_rule_annotation(input)?;
+        let rule_delimiter = macro_delimited!(rule in input);
+        // Calls to an annotated rule are recognized by its literal tokens, so
+        // an internal rule without any can't be told apart from public rules.
+        if annotation == Some(RuleAnnotation::Internal) && literal_prefix(&rule).is_empty() {
+            return Err(Error::new(
+                delimiter_span(&rule_delimiter),
+                "an `#[internal]` rule must start with literal tokens, like `(@name ...)`",
+            ));
+        }
         Ok(MacroRulesRule {
-            rule_delimiter: macro_delimited!(rule in input),
+            annotation,
+            rule_delimiter,
             rule,
             fat_arrow: input.parse::<Token![=>]>()?,
             body_delimiter: macro_delimited!(body in input),
@@ -0,0 +1,172 @@
+extern crate proc_macro2;
+
+use proc_macro2::Span;
+use std::path::Path;
+use syn::{Ident, LitStr};
+use toml::Value;
+
+use crate::args::{closest_match, Args};
+use crate::marker::{check_kind, Marker};
+use crate::naming::is_unique_template;
+use crate::reexport::parse_crate_path;
+
+// The names of all valid keys.
+const KEYS: &[&str] = &[
+    "mode",
+    "cfg",
+    "impl",
+    "internal",
+    "crate_path",
+    "local",
+    "qualify_self",
+    "qualify",
+    "local_inner_macros",
+    "split",
+    "seal",
+    "warn_direct",
+    "collision",
+];
+
+// Read the defaults from `[package.metadata.clean-macro-docs]` in the manifest
+// of the crate being compiled.
+pub fn manifest_defaults() -> Result<Args, String> {
+    match std::env::var_os("CARGO_MANIFEST_DIR") {
+        Some(dir) => read_manifest_defaults(Path::new(&dir)),
+        None => Ok(Args::default()),
+    }
+}
+
+// Read the defaults from the manifest in `dir`. Builds without Cargo may set
+// `CARGO_MANIFEST_DIR` without a manifest there, which is treated like a
+// manifest without defaults.
+pub fn read_manifest_defaults(dir: &Path) -> Result<Args, String> {
+    match std::fs::read_to_string(dir.join("Cargo.toml")) {
+        Ok(manifest) => parse_manifest_defaults(&manifest),
+        Err(_) => Ok(Args::default()),
+    }
+}
+
+pub fn parse_manifest_defaults(manifest: &str) -> Result<Args, String> {
+    let manifest = manifest
+        .parse::<Value>()
+        .map_err(|err| format!("failed to parse `Cargo.toml`: {}", err))?;
+
+    let metadata = if let Some(metadata) = manifest
+        .get("package")
+        .and_then(|package| package.get("metadata"))
+        .and_then(|metadata| metadata.get("clean-macro-docs"))
+    {
+        metadata
+    } else {
+        return Ok(Args::default());
+    };
+
+    let metadata = metadata
+        .as_table()
+        .ok_or("`package.metadata.clean-macro-docs` must be a table")?;
+
+    let mut defaults = Args::default();
+    for (key, value) in metadata {
+        let err = |msg: &str| format!("`package.metadata.clean-macro-docs.{}`: {}", key, msg);
+
+        if !KEYS.contains(&key.as_str()) {
+            let mut msg = format!("unknown key `package.metadata.clean-macro-docs.{}`", key);
+            if let Some(closest) = closest_match(key, KEYS) {
+                msg.push_str(&format!(", did you mean `{}`?", closest));
+            }
+            return Err(msg);
+        }
+
+        if key == "impl" {
+            defaults.priv_markers = Some(manifest_markers(value).map_err(|msg| err(&msg))?);
+            continue;
+        }
+
+        if key == "qualify" {
+            defaults.qualify = Some(manifest_idents(value).map_err(|msg| err(&msg))?);
+            continue;
+        }
+        if key == "qualify_self" {
+            defaults.qualify_self = Some(value.as_bool().ok_or_else(|| err("expected a boolean"))?);
+            continue;
+        }
+        if key == "local_inner_macros" {
+            defaults.local_inner_macros =
+                Some(value.as_bool().ok_or_else(|| err("expected a boolean"))?);
+            continue;
+        }
+        if key == "split" {
+            defaults.split = Some(value.as_bool().ok_or_else(|| err("expected a boolean"))?);
+            continue;
+        }
+        if key == "seal" {
+            defaults.seal = Some(value.as_bool().ok_or_else(|| err("expected a boolean"))?);
+            continue;
+        }
+        if key == "warn_direct" {
+            defaults.warn_direct = Some(value.as_bool().ok_or_else(|| err("expected a boolean"))?);
+            continue;
+        }
+
+        let value = value.as_str().ok_or_else(|| err("expected a string"))?;
+        match key.as_str() {
+            "mode" => defaults.mode = Some(value.parse().map_err(|msg: String| err(&msg))?),
+            "cfg" => {
+                defaults.cfg =
+                    Some(syn::parse_str(value).map_err(|_| err("expected cfg predicate"))?)
+            }
+            "internal" => {
+                if !is_unique_template(value) {
+                    return Err(err("expected a template containing `{name}` or `{hash}`"));
+                }
+                defaults.priv_ident = Some(LitStr::new(value, Span::call_site()))
+            }
+            "crate_path" => {
+                defaults.crate_path = Some(parse_crate_path(value).map_err(|msg| err(&msg))?)
+            }
+            "local" => defaults.local = Some(value.parse().map_err(|msg: String| err(&msg))?),
+            "collision" => {
+                defaults.collision = Some(value.parse().map_err(|msg: String| err(&msg))?)
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    Ok(defaults)
+}
+
+// An array of strings containing identifiers.
+fn manifest_idents(value: &Value) -> Result<Vec<Ident>, String> {
+    value
+        .as_array()
+        .ok_or("expected an array")?
+        .iter()
+        .map(|value| {
+            let value = value.as_str().ok_or("expected a string")?;
+            syn::parse_str(value).map_err(|_| format!("`{}` is not a valid identifier", value))
+        })
+        .collect()
+}
+
+// `impl` is a prefix marker, a table of `kind = "..."` markers, or an array of
+// those.
+fn manifest_markers(value: &Value) -> Result<Vec<Marker>, String> {
+    match value {
+        Value::String(value) => Ok(vec![Marker::new("prefix", value)?]),
+        Value::Table(table) => table
+            .iter()
+            .map(|(kind, value)| {
+                check_kind(kind)?;
+                Marker::new(kind, value.as_str().ok_or("expected a string")?)
+            })
+            .collect(),
+        Value::Array(values) => {
+            let mut priv_markers = Vec::new();
+            for value in values {
+                priv_markers.extend(manifest_markers(value)?);
+            }
+            Ok(priv_markers)
+        }
+        _ => Err("expected a string, table or array".to_string()),
+    }
+}
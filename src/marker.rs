@@ -0,0 +1,262 @@
+extern crate proc_macro2;
+
+use proc_macro2::{Delimiter, Group, Punct, Spacing, TokenStream, TokenTree};
+use std::str::FromStr;
+use syn::{Ident, Lit};
+
+use crate::args::closest_match;
+use crate::macro_rules::{MacroRulesRule, RuleAnnotation};
+
+// The names of all marker kinds.
+const MARKER_KINDS: &[&str] = &["prefix", "suffix", "wrapped", "literal"];
+
+pub fn check_kind(kind: &str) -> Result<(), String> {
+    if MARKER_KINDS.contains(&kind) {
+        Ok(())
+    } else if let Some(closest) = closest_match(kind, MARKER_KINDS) {
+        Err(format!(
+            "unknown marker kind `{}`, did you mean `{}`?",
+            kind, closest
+        ))
+    } else {
+        Err(format!("unknown marker kind `{}`", kind))
+    }
+}
+
+// An internal-rule marker. Token markers are kept as token trees so that they
+// can be matched against rules and invocations without stringifying them.
+#[derive(Clone)]
+pub enum Marker {
+    // `(marker ...)`
+    Prefix(Vec<TokenTree>),
+    // `(... marker)`
+    Suffix(Vec<TokenTree>),
+    // `([marker ...] ...)`, using any delimiter
+    Wrapped(Vec<TokenTree>),
+    // `("marker" ...)`
+    Literal(String),
+}
+
+// The marker used when `impl` isn't given: `@`.
+pub fn default_markers() -> Vec<Marker> {
+    vec![Marker::Prefix(vec![TokenTree::Punct(Punct::new(
+        '@',
+        Spacing::Joint,
+    ))])]
+}
+
+impl Marker {
+    // Create a marker of a kind that has been checked with `check_kind`.
+    pub fn new(kind: &str, value: &str) -> Result<Self, String> {
+        if kind == "literal" {
+            return Ok(Marker::Literal(value.to_string()));
+        }
+
+        let tokens: Vec<TokenTree> = TokenStream::from_str(value)
+            .map_err(|_| "invalid tokens".to_string())?
+            .into_iter()
+            .collect();
+        if tokens.is_empty() {
+            return Err("expected tokens".to_string());
+        }
+
+        match kind {
+            "prefix" => Ok(Marker::Prefix(tokens)),
+            "suffix" => Ok(Marker::Suffix(tokens)),
+            "wrapped" => Ok(Marker::Wrapped(tokens)),
+            _ => unreachable!(),
+        }
+    }
+
+    // Whether the rule, or invocation arguments, in `stream` are marked as
+    // internal.
+    pub fn matches(&self, stream: &TokenStream) -> bool {
+        match self {
+            Marker::Prefix(tokens) => starts_with(stream, tokens),
+            Marker::Suffix(tokens) => {
+                let stream: Vec<TokenTree> = stream.clone().into_iter().collect();
+                stream.len() >= tokens.len()
+                    && stream[stream.len() - tokens.len()..]
+                        .iter()
+                        .zip(tokens)
+                        .all(|(token, marker_token)| token_eq(marker_token, token))
+            }
+            Marker::Wrapped(tokens) => match stream.clone().into_iter().next() {
+                Some(TokenTree::Group(group)) if group.delimiter() != Delimiter::None => {
+                    starts_with(&group.stream(), tokens)
+                }
+                _ => false,
+            },
+            Marker::Literal(value) => match stream.clone().into_iter().next() {
+                Some(TokenTree::Literal(literal)) => match Lit::new(literal) {
+                    Lit::Str(literal) => literal.value() == *value,
+                    _ => false,
+                },
+                _ => false,
+            },
+        }
+    }
+}
+
+fn starts_with(stream: &TokenStream, tokens: &[TokenTree]) -> bool {
+    let mut stream = stream.clone().into_iter();
+    tokens.iter().all(|marker_token| match stream.next() {
+        Some(token) => token_eq(marker_token, &token),
+        None => false,
+    })
+}
+
+// Compare two token trees, ignoring spans and punct spacing.
+fn token_eq(a: &TokenTree, b: &TokenTree) -> bool {
+    match (a, b) {
+        (TokenTree::Ident(a), TokenTree::Ident(b)) => a == b,
+        (TokenTree::Punct(a), TokenTree::Punct(b)) => a.as_char() == b.as_char(),
+        (TokenTree::Literal(a), TokenTree::Literal(b)) => a.to_string() == b.to_string(),
+        (TokenTree::Group(a), TokenTree::Group(b)) => {
+            a.delimiter() == b.delimiter() && stream_eq(&a.stream(), &b.stream())
+        }
+        _ => false,
+    }
+}
+
+fn stream_eq(a: &TokenStream, b: &TokenStream) -> bool {
+    let mut a = a.clone().into_iter();
+    let mut b = b.clone().into_iter();
+    loop {
+        match (a.next(), b.next()) {
+            (Some(a), Some(b)) if token_eq(&a, &b) => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+// Decides whether rules and invocations are internal, based on the markers
+// and on the rules' `#[internal]` and `#[public]` annotations.
+pub struct Classifier {
+    priv_markers: Vec<Marker>,
+    // The literal tokens that annotated rules start with, and whether the
+    // rule is internal
+    annotated: Vec<(Vec<TokenTree>, bool)>,
+}
+
+impl Classifier {
+    pub fn new<'a>(
+        priv_markers: Vec<Marker>,
+        rules: impl IntoIterator<Item = &'a MacroRulesRule>,
+    ) -> Self {
+        let annotated = rules
+            .into_iter()
+            .filter_map(|rule| {
+                let internal = rule.annotation? == RuleAnnotation::Internal;
+                // Calls to a public rule without literal tokens are left to
+                // the markers. Internal rules always have some.
+                let prefix = literal_prefix(&rule.rule);
+                if prefix.is_empty() {
+                    None
+                } else {
+                    Some((prefix, internal))
+                }
+            })
+            .collect();
+
+        Classifier {
+            priv_markers,
+            annotated,
+        }
+    }
+
+    pub fn is_internal_rule(&self, rule: &MacroRulesRule) -> bool {
+        match rule.annotation {
+            Some(annotation) => annotation == RuleAnnotation::Internal,
+            None => self.is_marked(&rule.rule),
+        }
+    }
+
+    // Whether an invocation with the arguments in `stream` calls an internal
+    // rule. Invocations that start with an annotated rule's literal tokens are
+    // classified like that rule, preferring the longest match.
+    pub fn is_internal_invoc(&self, stream: &TokenStream) -> bool {
+        self.annotated
+            .iter()
+            .filter(|(prefix, _)| starts_with(stream, prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map_or_else(|| self.is_marked(stream), |(_, internal)| *internal)
+    }
+
+    // The sub-marker of an internal rule or invocation, if it has one.
+    pub fn sub_marker(&self, stream: &TokenStream) -> Option<SubMarker> {
+        self.priv_markers
+            .iter()
+            .find_map(|priv_marker| match priv_marker {
+                Marker::Prefix(tokens) if priv_marker.matches(stream) => {
+                    let mut rest = stream.clone().into_iter().skip(tokens.len());
+                    let ident = match rest.next() {
+                        Some(TokenTree::Ident(ident)) => ident,
+                        _ => return None,
+                    };
+                    let group = match rest.next() {
+                        Some(TokenTree::Group(group))
+                            if group.delimiter() != Delimiter::None
+                                && !contains_dollar(&TokenTree::Group(group.clone())) =>
+                        {
+                            Some(group)
+                        }
+                        _ => None,
+                    };
+                    Some(SubMarker { ident, group })
+                }
+                _ => None,
+            })
+    }
+
+    fn is_marked(&self, stream: &TokenStream) -> bool {
+        self.priv_markers
+            .iter()
+            .any(|priv_marker| priv_marker.matches(stream))
+    }
+}
+
+// What an internal rule or invocation starts with after its prefix marker,
+// for `split`.
+#[derive(Clone)]
+pub struct SubMarker {
+    // The identifier right after the prefix marker, like `parse` in
+    // `(@parse ...)`
+    pub ident: Ident,
+    // The delimited group right after the identifier, like `[0]` in
+    // `(@impl[0] ...)`, unless it has metavariables
+    pub group: Option<Group>,
+}
+
+impl PartialEq for SubMarker {
+    fn eq(&self, other: &Self) -> bool {
+        self.ident == other.ident
+            && match (&self.group, &other.group) {
+                (Some(a), Some(b)) => {
+                    a.delimiter() == b.delimiter() && stream_eq(&a.stream(), &b.stream())
+                }
+                (None, None) => true,
+                _ => false,
+            }
+    }
+}
+
+// The tokens a rule starts with, up to its first metavariable.
+pub fn literal_prefix(rule: &TokenStream) -> Vec<TokenTree> {
+    rule.clone()
+        .into_iter()
+        .take_while(|token| !contains_dollar(token))
+        .collect()
+}
+
+fn contains_dollar(token: &TokenTree) -> bool {
+    match token {
+        TokenTree::Punct(punct) => punct.as_char() == '$',
+        TokenTree::Group(group) => group
+            .stream()
+            .into_iter()
+            .any(|token| contains_dollar(&token)),
+        _ => false,
+    }
+}
@@ -0,0 +1,121 @@
+extern crate proc_macro2;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use std::str::FromStr;
+use syn::{parse_quote, Meta};
+
+/// The name of the environment variable that overrides every `mode` argument.
+pub const MODE_ENV_VAR: &str = "CLEAN_MACRO_DOCS_MODE";
+
+/// When `clean_docs` should transform a macro.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// Only transform when `rustdoc` sets the `doc` environment variable.
+    Doc,
+    /// Always transform.
+    Always,
+    /// Never transform.
+    Never,
+    /// Emit both versions, gated by a `cfg` predicate.
+    Cfg,
+}
+
+impl Mode {
+    // The mode of a macro with the arguments `mode` and `cfg`. The crate-wide
+    // override wins over the arguments. Setting `cfg` without `mode` implies
+    // `mode = "cfg"`.
+    pub fn resolve(mode: Option<Mode>, cfg: &Option<Meta>) -> Result<Mode, String> {
+        Ok(match mode_override()? {
+            Some(mode) => mode,
+            None => mode.unwrap_or(if cfg.is_some() { Mode::Cfg } else { Mode::Doc }),
+        })
+    }
+
+    // Whether to transform the macro in this build. With `mode = "cfg"`, both
+    // versions are emitted and the compiler picks one.
+    pub fn runs(self) -> bool {
+        match self {
+            Mode::Doc => std::env::var("doc").is_ok(),
+            Mode::Always | Mode::Cfg => true,
+            Mode::Never => false,
+        }
+    }
+
+    // With `mode = "cfg"`, the attribute that gates every transformed item,
+    // and the `untouched` items, gated to be used otherwise.
+    pub fn gate(self, cfg: Option<Meta>, untouched: &[TokenStream]) -> (TokenStream, TokenStream) {
+        if self != Mode::Cfg {
+            return (quote!(), quote!());
+        }
+        let cfg = cfg.unwrap_or_else(|| parse_quote!(doc));
+        (
+            quote!(#[cfg(#cfg)]),
+            quote! {
+                #(
+                    #[cfg(not(#cfg))]
+                    #untouched
+                )*
+            },
+        )
+    }
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "doc" => Ok(Mode::Doc),
+            "always" => Ok(Mode::Always),
+            "never" => Ok(Mode::Never),
+            "cfg" => Ok(Mode::Cfg),
+            _ => Err(format!(
+                "invalid mode `{}`, expected one of `doc`, `always`, `never` or `cfg`",
+                s
+            )),
+        }
+    }
+}
+
+// Get the crate-wide mode override, if any, from the environment variable and
+// the Cargo features.
+pub fn mode_override() -> Result<Option<Mode>, String> {
+    let env = std::env::var(MODE_ENV_VAR).ok();
+    resolve_override(
+        env.as_deref(),
+        cfg!(feature = "force-never"),
+        cfg!(feature = "force-cfg"),
+        cfg!(feature = "force-always"),
+    )
+}
+
+// The crate-wide mode override, given the value of the environment variable
+// and which `force-*` features are enabled.
+//
+// The environment variable takes precedence over the features. Among the
+// features, `force-never` takes precedence over `force-cfg`, which takes
+// precedence over `force-always`.
+pub fn resolve_override(
+    env: Option<&str>,
+    force_never: bool,
+    force_cfg: bool,
+    force_always: bool,
+) -> Result<Option<Mode>, String> {
+    if let Some(val) = env {
+        return val
+            .parse()
+            .map(Some)
+            .map_err(|err| format!("{}: {}", MODE_ENV_VAR, err));
+    }
+
+    Ok(if force_never {
+        Some(Mode::Never)
+    } else if force_cfg {
+        Some(Mode::Cfg)
+    } else if force_always {
+        Some(Mode::Always)
+    } else {
+        None
+    })
+}
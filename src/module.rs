@@ -0,0 +1,204 @@
+extern crate proc_macro2;
+
+use proc_macro2::TokenStream;
+use quote::{quote_spanned, ToTokens};
+use syn::punctuated::Punctuated;
+use syn::spanned::Spanned;
+use syn::{parse2, Attribute, Ident, Item, ItemMod, Result, Token};
+
+use crate::args::{Args, RawArg};
+use crate::clean_macro_rules;
+#[cfg(feature = "decl-macro")]
+use crate::decl_macro::{check_decl_macro_args, clean_decl_macro, DeclMacro};
+use crate::macro_rules::MacroRules;
+use crate::naming::is_unique_template;
+use crate::reexport::{check_internal_path, hidden_mod, lift, same_path, Reexport};
+
+// Apply `clean_docs` to every `macro_rules!` macro (and `macro` item, with the
+// `decl-macro` feature) in an inline module, including the ones in nested
+// inline modules. The module's arguments are used as defaults, and can be
+// overridden by a `#[clean_docs(...)]` attribute on an individual macro or
+// nested module. With `internal_path`, the re-exports of every macro in the
+// module and its nested modules go in a single hidden module, added to this
+// one.
+pub fn clean_mod(defaults: Args, mut item_mod: ItemMod) -> TokenStream {
+    let mut reexports = Vec::new();
+    if let Err(err) = clean_mod_items(&defaults, &mut item_mod, &mut reexports) {
+        return err;
+    }
+
+    if let (Some(internal_path), Some((_, items))) =
+        (&defaults.internal_path, &mut item_mod.content)
+    {
+        if !reexports.is_empty() {
+            items.push(Item::Verbatim(hidden_mod(internal_path, reexports)));
+        }
+    }
+
+    item_mod.into_token_stream()
+}
+
+// Clean the items of `item_mod`, pushing the re-exports for the hidden module
+// of `defaults.internal_path` to `reexports`. Nested modules with the same
+// `internal_path` add theirs, and the others get their own hidden module.
+fn clean_mod_items(
+    defaults: &Args,
+    item_mod: &mut ItemMod,
+    reexports: &mut Vec<Reexport>,
+) -> std::result::Result<(), TokenStream> {
+    if let Some(priv_ident) = &defaults.priv_ident {
+        if !is_unique_template(&priv_ident.value()) {
+            return Err(quote_spanned! {
+                priv_ident.span()=> compile_error!(
+                    "`internal` must be a template containing `{name}` or `{hash}` when set for a whole module"
+                );
+            });
+        }
+    }
+
+    let items = if let Some((_, items)) = &mut item_mod.content {
+        items
+    } else {
+        return Err(quote_spanned! {
+            item_mod.span()=> compile_error!("expected an inline module");
+        });
+    };
+
+    // Exported macros can call each other through `$crate::`, wherever they
+    // are in the module
+    let mut defaults = defaults.clone();
+    defaults
+        .qualify
+        .get_or_insert_with(Vec::new)
+        .extend(exported_macros(items));
+
+    for item in items.iter_mut() {
+        let cleaned = match item {
+            Item::Macro(item_macro) if item_macro.mac.path.is_ident("macro_rules") => {
+                let args = match take_args(&mut item_macro.attrs, &defaults)
+                    .and_then(|args| check_internal_path(&args, &defaults).map(|()| args))
+                {
+                    Ok(args) => args,
+                    Err(err) => {
+                        *item = Item::Verbatim(err.to_compile_error());
+                        continue;
+                    }
+                };
+                match parse2::<MacroRules>(item_macro.to_token_stream()) {
+                    Ok(mac_rules) => clean_macro_rules(args, mac_rules, reexports, None),
+                    Err(err) => err.to_compile_error(),
+                }
+            }
+            #[cfg(feature = "decl-macro")]
+            Item::Macro2(item_macro) => {
+                let checked = match item_macro
+                    .attrs
+                    .iter()
+                    .find(|attr| is_clean_docs_attr(attr))
+                {
+                    Some(attr) => raw_args(attr).and_then(|args| check_decl_macro_args(&args)),
+                    None => Ok(()),
+                };
+                let args = match checked.and_then(|()| take_args(&mut item_macro.attrs, &defaults))
+                {
+                    Ok(args) => args,
+                    Err(err) => {
+                        *item = Item::Verbatim(err.to_compile_error());
+                        continue;
+                    }
+                };
+                match parse2::<DeclMacro>(item_macro.to_token_stream()) {
+                    Ok(decl_macro) => clean_decl_macro(args, decl_macro),
+                    Err(err) => err.to_compile_error(),
+                }
+            }
+            Item::Mod(inner_mod) if inner_mod.content.is_some() => {
+                let args = match take_args(&mut inner_mod.attrs, &defaults) {
+                    Ok(args) => args,
+                    Err(err) => {
+                        *item = Item::Verbatim(err.to_compile_error());
+                        continue;
+                    }
+                };
+                let same_tree = match (&args.internal_path, &defaults.internal_path) {
+                    (Some(path), Some(default)) => same_path(path, default),
+                    _ => false,
+                };
+                if !same_tree {
+                    clean_mod(args, inner_mod.clone())
+                } else {
+                    // The internal macros are re-exported from each module
+                    // on the way to the hidden module
+                    let mut inner_reexports = Vec::new();
+                    if let Err(err) = clean_mod_items(&args, inner_mod, &mut inner_reexports) {
+                        *item = Item::Verbatim(err);
+                        continue;
+                    }
+                    if let Some((_, inner_items)) = &mut inner_mod.content {
+                        let lifted = lift(&inner_mod.ident, &mut inner_reexports);
+                        inner_items.extend(lifted.into_iter().map(Item::Verbatim));
+                    }
+                    reexports.extend(inner_reexports);
+                    continue;
+                }
+            }
+            _ => continue,
+        };
+        *item = Item::Verbatim(cleaned);
+    }
+
+    Ok(())
+}
+
+// The names of the `#[macro_export]`ed macros in `items`, including the ones in
+// nested inline modules.
+fn exported_macros(items: &[Item]) -> Vec<Ident> {
+    let mut names = Vec::new();
+    for item in items {
+        match item {
+            Item::Macro(item_macro)
+                if item_macro
+                    .attrs
+                    .iter()
+                    .any(|attr| attr.path.is_ident("macro_export")) =>
+            {
+                names.extend(item_macro.ident.clone());
+            }
+            Item::Mod(ItemMod {
+                content: Some((_, items)),
+                ..
+            }) => names.extend(exported_macros(items)),
+            _ => {}
+        }
+    }
+    names
+}
+
+// Remove the `#[clean_docs(...)]` attribute from `attrs`, if there is one,
+// and merge its arguments with `defaults`.
+pub fn take_args(attrs: &mut Vec<Attribute>, defaults: &Args) -> Result<Args> {
+    let pos = if let Some(pos) = attrs.iter().position(is_clean_docs_attr) {
+        pos
+    } else {
+        return Ok(defaults.clone());
+    };
+
+    let attr = attrs.remove(pos);
+    Args::parse(raw_args(&attr)?).map(|args| args.with_defaults(defaults))
+}
+
+// The arguments of a `#[clean_docs(...)]` attribute, as written.
+fn raw_args(attr: &Attribute) -> Result<Vec<RawArg>> {
+    if attr.tokens.is_empty() {
+        return Ok(Vec::new());
+    }
+    attr.parse_args_with(Punctuated::<RawArg, Token![,]>::parse_terminated)
+        .map(|args| args.into_iter().collect())
+}
+
+pub fn is_clean_docs_attr(attr: &Attribute) -> bool {
+    attr.path
+        .segments
+        .last()
+        .is_some_and(|segment| segment.ident == "clean_docs")
+}
@@ -0,0 +1,141 @@
+extern crate proc_macro2;
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote_spanned};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use syn::{Attribute, Ident};
+
+// The template used when `internal` isn't given.
+pub const DEFAULT_TEMPLATE: &str = "__{name}";
+
+// Whether `template` names each macro differently, so that it can be used for
+// a whole module or crate.
+pub fn is_unique_template(template: &str) -> bool {
+    template.contains("{name}") || template.contains("{hash}")
+}
+
+// Expand an `internal` template for the macro `pub_ident`, defined as
+// `definition`.
+//
+// - `{name}` is the macro's name, without `r#`.
+// - `{crate}` is the name of the crate being compiled.
+// - `{hash}` is a hash of the crate name and the macro's definition.
+//
+// If the result is a keyword, a raw identifier is returned.
+pub fn expand_template(
+    template: &str,
+    pub_ident: &Ident,
+    definition: &TokenStream,
+) -> Result<Ident, String> {
+    let mut name = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        name.push_str(&rest[..start]);
+        let end = rest[start..]
+            .find('}')
+            .map(|end| start + end)
+            .ok_or_else(|| format!("unclosed `{{` in `{}`", template))?;
+
+        match &rest[start + 1..end] {
+            "name" => name.push_str(&unraw(pub_ident)),
+            "crate" => name.push_str(&crate_name()?),
+            "hash" => name.push_str(&hash(definition)),
+            placeholder => {
+                return Err(format!(
+                    "unknown placeholder `{{{}}}`, expected `{{name}}`, `{{crate}}` or `{{hash}}`",
+                    placeholder
+                ))
+            }
+        }
+        rest = &rest[end + 1..];
+    }
+    name.push_str(rest);
+
+    syn::parse_str::<Ident>(&name)
+        .or_else(|_| syn::parse_str::<Ident>(&format!("r#{}", name)))
+        .map_err(|_| format!("`{}` is not a valid identifier", name))
+}
+
+fn unraw(ident: &Ident) -> String {
+    let name = ident.to_string();
+    match name.strip_prefix("r#") {
+        Some(name) => name.to_string(),
+        None => name,
+    }
+}
+
+fn crate_name() -> Result<String, String> {
+    std::env::var("CARGO_CRATE_NAME").map_err(|_| "`{crate}` requires Cargo".to_string())
+}
+
+fn hash(definition: &TokenStream) -> String {
+    let mut hasher = DefaultHasher::new();
+    std::env::var("CARGO_CRATE_NAME")
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    definition.to_string().hash(&mut hasher);
+    format!("{:016x}", hasher.finish())
+}
+
+/// What to do about the internal macro's name being taken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Collision {
+    /// Let the compiler report the name as defined twice.
+    Error,
+    /// Append a hash of the macro's definition to the name.
+    Rename,
+}
+
+impl FromStr for Collision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "error" => Ok(Collision::Error),
+            "rename" => Ok(Collision::Rename),
+            _ => Err(format!(
+                "invalid collision handling `{}`, expected `error` or `rename`",
+                s
+            )),
+        }
+    }
+}
+
+// An item that claims `name` at the crate root for the macro `owner`, in
+// every build. When two macros claim the same name, the compiler reports the
+// guard as defined twice, pointing at both macros. The `cfg` attributes of the
+// owner are copied, so that alternative definitions of it don't collide.
+pub fn name_guard(name: &Ident, owner: &Ident, cfgs: &[&Attribute]) -> TokenStream {
+    let guard = format_ident!(
+        "__clean_macro_docs_name_{}",
+        unraw(name),
+        span = owner.span()
+    );
+    quote_spanned! {owner.span()=>
+        #(#cfgs)*
+        #[doc(hidden)]
+        #[macro_export]
+        macro_rules! #guard {
+            () => {};
+        }
+    }
+}
+
+// The name used instead of `name` with `collision = "rename"`.
+pub fn rename(name: &Ident, definition: &TokenStream) -> Ident {
+    format_ident!("{}_{}", name, hash(definition))
+}
+
+// The identifier that sealed internal rules and their invocations start with.
+// It's a hash of the crate's name and version, so that every macro of the
+// crate uses the same one, and other crates can't rely on it.
+pub fn seal_ident() -> Ident {
+    let mut hasher = DefaultHasher::new();
+    for var in &["CARGO_CRATE_NAME", "CARGO_PKG_VERSION"] {
+        std::env::var(var).unwrap_or_default().hash(&mut hasher);
+    }
+    format_ident!("__sealed_{:016x}", hasher.finish())
+}
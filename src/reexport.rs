@@ -0,0 +1,129 @@
+extern crate proc_macro2;
+
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::{Error, Ident, Path};
+
+use crate::args::Args;
+
+// A `pub use` of an internal macro `name` as `alias`, to be placed in the
+// hidden module of `internal_path`.
+pub struct Reexport {
+    // The modules from the hidden module's parent to the one where `name` can
+    // be named, empty when it's the macro's own
+    pub path: Vec<Ident>,
+    // `#[cfg]`s and lint attributes for each `pub use`
+    pub attrs: TokenStream,
+    pub name: Ident,
+    pub alias: Ident,
+}
+
+// The macros of a module share the hidden module of `internal_path`, so it can
+// only be set for a whole module. `defaults` are the arguments of the module,
+// if any, which macros in it can repeat.
+pub fn check_internal_path(args: &Args, defaults: &Args) -> syn::Result<()> {
+    match (&args.internal_path, &defaults.internal_path) {
+        (Some(path), Some(default)) if same_path(path, default) => Ok(()),
+        (Some(path), _) => Err(Error::new_spanned(
+            path,
+            "`internal_path` can only be set for a whole module, which shares the hidden module between its macros",
+        )),
+        (None, _) => Ok(()),
+    }
+}
+
+pub fn same_path(a: &Path, b: &Path) -> bool {
+    a.segments
+        .iter()
+        .map(|segment| &segment.ident)
+        .eq(b.segments.iter().map(|segment| &segment.ident))
+}
+
+// Parse the value of `internal_path`, a path to a module relative to the crate
+// root.
+pub fn parse_internal_path(value: &str) -> Result<Path, String> {
+    const EXPECTED: &str = "expected a module path relative to the crate root, like `macros::__private`";
+
+    let path = syn::parse_str::<Path>(value).map_err(|_| EXPECTED.to_string())?;
+    let relative = path.leading_colon.is_none()
+        && path.segments.iter().all(|segment| {
+            segment.arguments.is_empty()
+                && !["crate", "self", "super", "Self"].contains(&&*segment.ident.to_string())
+        });
+    if relative {
+        Ok(path)
+    } else {
+        Err(EXPECTED.to_string())
+    }
+}
+
+// Parse the value of `crate_path`, an absolute path that replaces `$crate`.
+pub fn parse_crate_path(value: &str) -> Result<Path, String> {
+    const EXPECTED: &str = "expected an absolute path, like `::foo::__macros`";
+
+    let path = syn::parse_str::<Path>(value).map_err(|_| EXPECTED.to_string())?;
+    let absolute = path.leading_colon.is_some()
+        && path
+            .segments
+            .iter()
+            .all(|segment| segment.arguments.is_empty());
+    if absolute {
+        Ok(path)
+    } else {
+        Err(EXPECTED.to_string())
+    }
+}
+
+// Make the internal macros of `reexports`, which are defined in the module
+// `module` or below it, nameable from its parent, by re-exporting them from
+// `module`. Returns the items to add to `module`.
+pub fn lift(module: &Ident, reexports: &mut [Reexport]) -> Vec<TokenStream> {
+    reexports
+        .iter_mut()
+        .map(|reexport| {
+            let Reexport {
+                path, attrs, name, ..
+            } = reexport;
+            let item = quote! {
+                #attrs
+                #[doc(hidden)]
+                pub use #(#path::)* #name;
+            };
+            *path = vec![module.clone()];
+            item
+        })
+        .collect()
+}
+
+// Emit the `#[doc(hidden)] pub mod` of `internal_path`, with all of
+// `reexports`. Macros of the same module are named by their textual scope,
+// and the others through the modules that re-export them.
+pub fn hidden_mod(internal_path: &Path, reexports: Vec<Reexport>) -> TokenStream {
+    let module = &internal_path.segments.last().unwrap().ident;
+    let items = reexports.into_iter().map(|reexport| {
+        let Reexport {
+            path,
+            attrs,
+            name,
+            alias,
+        } = reexport;
+        if path.is_empty() {
+            quote! {
+                #attrs
+                pub use #name as #alias;
+            }
+        } else {
+            quote! {
+                #attrs
+                pub use super::#(#path::)* #name as #alias;
+            }
+        }
+    });
+
+    quote! {
+        #[doc(hidden)]
+        pub mod #module {
+            #(#items)*
+        }
+    }
+}
@@ -1,48 +1,123 @@
 extern crate proc_macro2;
 
 use if_chain::if_chain;
-use proc_macro2::{Delimiter, Punct, Spacing, Span, TokenStream, TokenTree};
+use proc_macro2::{Delimiter, Group, Punct, Spacing, Span, TokenStream, TokenTree};
+use quote::ToTokens;
+use std::cell::RefCell;
 use std::iter::FromIterator;
-use syn::Ident;
+use std::ops::RangeInclusive;
+use syn::{Ident, Path};
 
-// Replace all occurences of `pub_ident!(priv_marker ...)` (using any delimiter)
-// with `priv_ident!(priv_marker ...)`.
-pub fn replace_macro_invocs(
+use crate::marker::Classifier;
+use crate::split::SubMacro;
+
+// The invocations to rewrite in the rules of the macro `pub_ident`.
+pub struct Rewrites<'a> {
+    pub pub_ident: &'a Ident,
+    pub priv_ident: &'a Ident,
+    // Replaces `pub_ident` and `$crate::pub_ident` in invocations of internal
+    // rules. Without it, only `pub_ident` is replaced with `priv_ident`.
+    pub priv_path: Option<TokenStream>,
+    // Replaces `pub_ident` in invocations of public rules
+    pub self_path: Option<TokenStream>,
+    // Other macros, and the paths that replace their names
+    pub qualify: Vec<(Ident, TokenStream)>,
+    // Other macros whose internal rules are called
+    pub calls: Vec<Call>,
+    // With `split`, the internal macros that invocations of internal rules
+    // are sent to by sub-marker, instead of `priv_ident`
+    pub split: &'a [SubMacro],
+    // With `seal`, the identifier added before the arguments of every
+    // rewritten invocation of an internal rule
+    pub seal: Option<Ident>,
+    pub classifier: &'a Classifier,
+}
+
+// Another macro, and what replaces its name in invocations of its internal
+// rules, as for `Rewrites::priv_ident` and `Rewrites::priv_path`.
+pub struct Call {
+    pub pub_ident: Ident,
+    pub priv_ident: Ident,
+    pub priv_path: Option<TokenStream>,
+}
+
+// Replace all occurences of `pub_ident!(...)` and `$crate::pub_ident!(...)`
+// (using any delimiter) that call an internal rule with `priv_path!(...)`, or
+// with `priv_ident!(...)` if there's no `priv_path`. Invocations through any
+// other path, such as `self::pub_ident!(...)`, keep their path.
+//
+// Invocations of the internal rules of the macros in `calls` are replaced the
+// same way. Invocations of public rules, and of the macros in `qualify`, are
+// replaced with their paths, unless they already use a path.
+pub fn replace_macro_invocs(stream: TokenStream, rewrites: &Rewrites) -> TokenStream {
+    rewrite_invocs(stream, &|preceding, ident, group| {
+        rewrites.replacement(preceding, ident, group)
+    })
+}
+
+// Remove `$crate::` from invocations of the macros in `idents`. The local copy
+// of an exported macro uses this to call the local copies, since the crate
+// can't call its own macro-expanded exported macros by absolute path.
+pub fn unqualify_macro_invocs(stream: TokenStream, idents: &[&Ident]) -> TokenStream {
+    rewrite_invocs(stream, &|preceding, ident, _| {
+        if !idents.contains(&ident) {
+            return None;
+        }
+        let i = preceding.len();
+        let len = crate_prefix_len(preceding)?;
+        Some((i - len..=i, TokenTree::Ident(ident.clone()).into()))
+    })
+}
+
+// The macros invoked in `stream` without a path, like `format!(...)`, in
+// order. Invocations of metavariables, like `$m!(...)`, are skipped.
+pub fn bare_macro_invocs(stream: TokenStream) -> Vec<Ident> {
+    let invocs = RefCell::new(Vec::new());
+    rewrite_invocs(stream, &|preceding, ident, _| {
+        if !is_metavar(preceding) && !follows_path(preceding) {
+            invocs.borrow_mut().push(ident.clone());
+        }
+        None
+    });
+    invocs.into_inner()
+}
+
+// The range of the tokens before an invocation `ident!group`, and of `ident`,
+// to replace, and what to replace them with.
+type Replacement = Option<(RangeInclusive<usize>, TokenStream)>;
+
+// Replace the invocations in `stream`, including the ones in nested groups,
+// as `replacement` decides. Groups without delimiters, which come from the
+// metavariables of another macro, are searched too.
+fn rewrite_invocs(
     stream: TokenStream,
-    pub_ident: &Ident,
-    priv_ident: &Ident,
-    priv_marker: &TokenStream,
+    replacement: &impl Fn(&[TokenTree], &Ident, &Group) -> Replacement,
 ) -> TokenStream {
     let mut tokens: Vec<TokenTree> = stream.into_iter().collect();
 
     let mut i = 0;
     while i < tokens.len() {
         if let TokenTree::Group(group) = &tokens[i] {
-            tokens[i] = TokenTree::Group(proc_macro2::Group::new(
+            let mut rewritten = Group::new(
                 group.delimiter(),
-                replace_macro_invocs(group.stream(), pub_ident, priv_ident, priv_marker),
-            ));
+                rewrite_invocs(group.stream(), replacement),
+            );
+            rewritten.set_span(group.span());
+            tokens[i] = TokenTree::Group(rewritten);
         } else if let TokenTree::Ident(ident) = &tokens[i] {
             if_chain! {
-                if tokens.len() - i >= 3 && ident == pub_ident;
+                if tokens.len() - i >= 3;
                 if let TokenTree::Punct(punct) = &tokens[i + 1];
                 if punct.as_char() == '!';
-                // pub_ident! ...
+                // ident! ...
                 if let TokenTree::Group(group) = &tokens[i + 2];
                 if group.delimiter() != Delimiter::None;
-                // pub_ident!( ... )
-                if group.stream().to_string().starts_with(&priv_marker.to_string());
+                // ident!( ... )
+                if let Some((replaced, path)) = replacement(&tokens[..i], ident, group);
                 then {
-                    tokens.splice(i..=i, vec![
-                        // Use Spacing::Alone for the `$` to make string-based
-                        // tests work correctly.
-                        TokenTree::Punct(Punct::new('$', Spacing::Alone)),
-                        TokenTree::Ident(Ident::new("crate", Span::call_site())),
-                        TokenTree::Punct(Punct::new(':', Spacing::Joint)),
-                        TokenTree::Punct(Punct::new(':', Spacing::Alone)),
-                        TokenTree::Ident(priv_ident.clone()),
-                    ]);
-                    i += 4;
+                    let path: Vec<TokenTree> = path.into_iter().collect();
+                    i = replaced.start() + path.len() - 1;
+                    tokens.splice(replaced, path);
                 }
             }
         }
@@ -50,3 +125,157 @@ pub fn replace_macro_invocs(
     }
     TokenStream::from_iter(tokens)
 }
+
+impl Rewrites<'_> {
+    // The range of `preceding` and `ident` to replace in the invocation
+    // `ident!group`, and what to replace it with.
+    fn replacement(&self, preceding: &[TokenTree], ident: &Ident, group: &Group) -> Replacement {
+        let i = preceding.len();
+
+        if ident != self.pub_ident {
+            let call = self.calls.iter().find(|call| call.pub_ident == *ident);
+            if let Some(call) = call {
+                if self.classifier.is_internal_invoc(&group.stream()) {
+                    let replacement =
+                        internal_replacement(preceding, &call.priv_ident, call.priv_path.as_ref());
+                    return Some(self.sealed(replacement, group));
+                }
+            }
+            return self
+                .qualify
+                .iter()
+                .find(|(name, _)| name == ident)
+                .filter(|_| !follows_path(preceding) && !is_metavar(preceding))
+                .map(|(_, path)| (i..=i, path.clone()));
+        }
+
+        if !self.classifier.is_internal_invoc(&group.stream()) {
+            // `pub_ident!(...)` calling a public rule, but not `$pub_ident!(...)`
+            return self
+                .self_path
+                .clone()
+                .filter(|_| !follows_path(preceding) && !is_metavar(preceding))
+                .map(|self_path| (i..=i, self_path));
+        }
+
+        // With `split`, the internal macro of the invocation's sub-marker, or
+        // of its identifier if there's none for the whole sub-marker
+        let sub = self.classifier.sub_marker(&group.stream());
+        let sub_macro = sub.and_then(|sub| {
+            self.split
+                .iter()
+                .find(|sub_macro| sub_macro.sub == sub)
+                .or_else(|| {
+                    self.split.iter().find(|sub_macro| {
+                        sub_macro.sub.ident == sub.ident && sub_macro.sub.group.is_none()
+                    })
+                })
+        });
+        let (priv_ident, priv_path) = match sub_macro {
+            Some(sub_macro) => (&sub_macro.priv_ident, sub_macro.priv_path.as_ref()),
+            None => (self.priv_ident, self.priv_path.as_ref()),
+        };
+        let replacement = internal_replacement(preceding, priv_ident, priv_path);
+        Some(self.sealed(replacement, group))
+    }
+
+    // Add the seal before the arguments `group` of an invocation of an
+    // internal rule, if there is one. The whole invocation is replaced then,
+    // so its arguments are rewritten here.
+    fn sealed(
+        &self,
+        (replaced, path): (RangeInclusive<usize>, TokenStream),
+        group: &Group,
+    ) -> (RangeInclusive<usize>, TokenStream) {
+        let seal = match &self.seal {
+            Some(seal) => seal,
+            None => return (replaced, path),
+        };
+
+        let mut args = TokenStream::from(TokenTree::Ident(seal.clone()));
+        args.extend(replace_macro_invocs(group.stream(), self));
+        let mut sealed = Group::new(group.delimiter(), args);
+        sealed.set_span(group.span());
+
+        let mut path = path;
+        path.extend(vec![
+            TokenTree::Punct(Punct::new('!', Spacing::Alone)),
+            TokenTree::Group(sealed),
+        ]);
+        (*replaced.start()..=*replaced.end() + 2, path)
+    }
+}
+
+// The replacement for an invocation of an internal rule, preceded by
+// `preceding`.
+fn internal_replacement(
+    preceding: &[TokenTree],
+    priv_ident: &Ident,
+    priv_path: Option<&TokenStream>,
+) -> (RangeInclusive<usize>, TokenStream) {
+    let i = preceding.len();
+    match (priv_path, crate_prefix_len(preceding)) {
+        // `$crate::pub_ident!(...)`
+        (Some(priv_path), Some(len)) => (i - len..=i, priv_path.clone()),
+        // `pub_ident!(...)`
+        (Some(priv_path), None) if !follows_path(preceding) => (i..=i, priv_path.clone()),
+        _ => (i..=i, TokenTree::Ident(priv_ident.clone()).into()),
+    }
+}
+
+// The tokens `$crate::segment::...::ident`, or `krate::segment::...::ident`
+// if `krate` is given.
+pub fn macro_path<'a>(
+    krate: Option<&Path>,
+    segments: impl IntoIterator<Item = &'a Ident>,
+    ident: &'a Ident,
+) -> TokenStream {
+    let mut tokens: Vec<TokenTree> = match krate {
+        Some(krate) => krate.to_token_stream().into_iter().collect(),
+        // Use Spacing::Alone for the `$` to make string-based tests work
+        // correctly.
+        None => vec![
+            TokenTree::Punct(Punct::new('$', Spacing::Alone)),
+            TokenTree::Ident(Ident::new("crate", Span::call_site())),
+        ],
+    };
+    for segment in segments.into_iter().chain(Some(ident)) {
+        tokens.push(TokenTree::Punct(Punct::new(':', Spacing::Joint)));
+        tokens.push(TokenTree::Punct(Punct::new(':', Spacing::Alone)));
+        tokens.push(TokenTree::Ident(segment.clone()));
+    }
+    TokenStream::from_iter(tokens)
+}
+
+// The number of tokens in the `$crate::` that `tokens` ends with, if it does.
+// In the expansion of another macro, `$crate` is a single identifier.
+fn crate_prefix_len(tokens: &[TokenTree]) -> Option<usize> {
+    if !follows_path(tokens) {
+        return None;
+    }
+    match &tokens[..tokens.len() - 2] {
+        [.., TokenTree::Punct(dollar), TokenTree::Ident(krate)]
+            if dollar.as_char() == '$' && krate == "crate" =>
+        {
+            Some(4)
+        }
+        [.., TokenTree::Ident(krate)] if krate == "$crate" => Some(3),
+        _ => None,
+    }
+}
+
+// Whether the invocation after `tokens` is of a metavariable, like `$m!(...)`.
+fn is_metavar(tokens: &[TokenTree]) -> bool {
+    match tokens.last() {
+        Some(TokenTree::Punct(punct)) => punct.as_char() == '$',
+        _ => false,
+    }
+}
+
+// Whether `tokens` ends with `::`.
+fn follows_path(tokens: &[TokenTree]) -> bool {
+    match tokens {
+        [.., TokenTree::Punct(a), TokenTree::Punct(b)] => a.as_char() == ':' && b.as_char() == ':',
+        _ => false,
+    }
+}
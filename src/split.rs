@@ -0,0 +1,128 @@
+extern crate proc_macro2;
+
+use proc_macro2::TokenStream;
+use quote::format_ident;
+use std::iter;
+use syn::punctuated::Punctuated;
+use syn::{Ident, Token};
+
+use crate::macro_rules::MacroRulesRule;
+use crate::marker::{Classifier, SubMarker};
+
+// An internal macro with the rules for one sub-marker, for `split`.
+pub struct SubMacro {
+    // The sub-marker, like `parse` in `(@parse ...)`, or `impl[0]` in
+    // `(@impl[0] ...)`
+    pub sub: SubMarker,
+    // `mac_parse`, the name it's re-exported as with `internal_path`
+    pub pub_ident: Ident,
+    // `__mac_parse`, for the internal macro `__mac`
+    pub priv_ident: Ident,
+    // How it's called, as for `Rewrites::priv_path`
+    pub priv_path: Option<TokenStream>,
+}
+
+// The internal macros to split the internal rules of the macro `pub_ident`
+// into, called through `priv_path`. There is one for each sub-marker with a
+// group, like `impl[0]`, and one for each identifier on its own, like `impl`,
+// for the invocations whose group isn't known, in the order they first
+// appear. A macro that would get every internal rule isn't needed.
+pub fn sub_macros<'a>(
+    classifier: &Classifier,
+    pub_ident: &Ident,
+    priv_ident: &Ident,
+    rules: impl IntoIterator<Item = &'a MacroRulesRule>,
+    priv_path: impl Fn(&Ident, &Ident) -> Option<TokenStream>,
+) -> Vec<SubMacro> {
+    let rule_subs: Vec<Option<SubMarker>> = rules
+        .into_iter()
+        .filter(|rule| classifier.is_internal_rule(rule))
+        .map(|rule| classifier.sub_marker(&rule.rule))
+        .collect();
+
+    let mut subs: Vec<SubMarker> = Vec::new();
+    for sub in rule_subs.iter().flatten() {
+        let ident_only = SubMarker {
+            ident: sub.ident.clone(),
+            group: None,
+        };
+        for sub in iter::once(ident_only).chain(sub.group.as_ref().map(|_| sub.clone())) {
+            if !subs.contains(&sub) {
+                subs.push(sub);
+            }
+        }
+    }
+    subs.retain(|sub| {
+        !rule_subs
+            .iter()
+            .all(|rule_sub| accepts(sub, rule_sub.as_ref()))
+    });
+
+    let mut names: Vec<String> = Vec::new();
+    subs.into_iter()
+        .map(|sub| {
+            // Groups like `[a b]` and `[a_b]` get the same name otherwise
+            let mut name = sub_name(&sub);
+            if names.contains(&name) {
+                name = format!("{}_{}", name, names.len());
+            }
+            names.push(name.clone());
+
+            let pub_ident = format_ident!("{}_{}", pub_ident, name);
+            let priv_ident = format_ident!("{}_{}", priv_ident, name);
+            SubMacro {
+                priv_path: priv_path(&pub_ident, &priv_ident),
+                sub,
+                pub_ident,
+                priv_ident,
+            }
+        })
+        .collect()
+}
+
+// The internal rules that an invocation sent to the internal macro of `sub`
+// can match, in order.
+pub fn sub_rules(
+    classifier: &Classifier,
+    priv_rules: &Punctuated<MacroRulesRule, Token![;]>,
+    sub: &SubMarker,
+) -> Punctuated<MacroRulesRule, Token![;]> {
+    let mut rules: Punctuated<MacroRulesRule, Token![;]> = priv_rules
+        .iter()
+        .filter(|rule| accepts(sub, classifier.sub_marker(&rule.rule).as_ref()))
+        .cloned()
+        .collect();
+    if priv_rules.trailing_punct() {
+        rules.push_punct(Default::default());
+    }
+    rules
+}
+
+// Whether the internal macro of `sub` needs a rule with the sub-marker
+// `rule_sub`. Rules without a sub-marker, or without a group, could match
+// any invocation with the same identifier, and an invocation without a known
+// group could match any group.
+fn accepts(sub: &SubMarker, rule_sub: Option<&SubMarker>) -> bool {
+    match rule_sub {
+        None => true,
+        Some(rule_sub) if rule_sub.ident != sub.ident => false,
+        Some(rule_sub) => sub.group.is_none() || rule_sub.group.is_none() || rule_sub == sub,
+    }
+}
+
+// The suffix of the internal macro of `sub`: `parse` for `@parse`, and
+// `impl_0` for `@impl[0]`.
+fn sub_name(sub: &SubMarker) -> String {
+    let ident = sub.ident.to_string();
+    let mut name = ident.trim_start_matches("r#").to_string();
+    if let Some(group) = &sub.group {
+        let group = group.stream().to_string();
+        let words: Vec<&str> = group
+            .split(|c: char| !c.is_ascii_alphanumeric() && c != '_')
+            .filter(|word| !word.is_empty())
+            .collect();
+        name.push('_');
+        name.push_str(&words.join("_"));
+    }
+    name
+}
@@ -1,9 +1,27 @@
+use crate::args::RawArg;
+use crate::clean_docs_impl;
+use syn::parse::Parser;
+use syn::punctuated::Punctuated;
+use syn::Token;
+
+// Expand `item` with the arguments `args`, for tests that don't need to match
+// the whole output.
+fn expand(args: &str, item: &str) -> String {
+    let args = Punctuated::<RawArg, Token![,]>::parse_terminated
+        .parse_str(args)
+        .unwrap()
+        .into_iter()
+        .collect();
+    clean_docs_impl(args, syn::parse_str(item).unwrap()).to_string()
+}
+
 mod expected_output {
+    use crate::args::RawArg;
     use crate::clean_docs_impl;
     use quote::quote;
     use syn::parse::Parser;
     use syn::punctuated::Punctuated;
-    use syn::{parse2, NestedMeta, Token};
+    use syn::{parse2, Token};
 
     macro_rules! make_test {
         (
@@ -20,7 +38,7 @@ mod expected_output {
         ) => {
             #[test]
             fn $name() {
-                let args = Punctuated::<NestedMeta, Token![,]>::parse_terminated
+                let args = Punctuated::<RawArg, Token![,]>::parse_terminated
                     .parse2(quote! {
                         $($args)*
                     })
@@ -83,6 +101,18 @@ mod expected_output {
                     simple_macro!(@impl $e)
                 };
             }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_simple_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___simple_macro {
+                () => {};
+            }
         )
     }
 
@@ -125,6 +155,18 @@ mod expected_output {
                     custom_impl_macro!(^internal $e)
                 };
             }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_custom_impl_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___custom_impl_macro {
+                () => {};
+            }
         )
     }
 
@@ -185,6 +227,18 @@ mod expected_output {
                     multiple_internal_macro!(@impl[0] $e)
                 };
             }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_multiple_internal_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___multiple_internal_macro {
+                () => {};
+            }
         )
     }
 
@@ -263,6 +317,18 @@ mod expected_output {
                     multiple_public_macro!(@impl[brackets] $e)
                 };
             }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_multiple_public_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___multiple_public_macro {
+                () => {};
+            }
         )
     }
 
@@ -284,6 +350,12 @@ mod expected_output {
                     format!("{}", $e)
                 };
             }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_no_internal_macro {
+                () => {};
+            }
         )
     }
 
@@ -326,6 +398,2366 @@ mod expected_output {
                     custom_internal_macro!(@impl $e)
                 };
             }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_custom_internal_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name__intern_mac {
+                () => {};
+            }
+        )
+    }
+
+    make_test! { [cfg]
+        input (
+            #[clean_docs(cfg = "any(doc, docsrs)")]
+            #[macro_export]
+            macro_rules! cfg_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    cfg_macro!(@impl $e)
+                };
+            }
+        )
+
+        expect (
+            #[cfg(any(doc, docsrs))]
+            #[macro_export]
+            macro_rules! cfg_macro {
+                ($e:expr) => {
+                    $crate::__cfg_macro!(@impl $e)
+                };
+            }
+
+            #[cfg(any(doc, docsrs))]
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __cfg_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+            }
+
+            #[cfg(any(doc, docsrs))]
+            #[allow(unused_macros)]
+            macro_rules! cfg_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    cfg_macro!(@impl $e)
+                };
+            }
+
+            #[cfg(not(any(doc, docsrs)))]
+            #[macro_export]
+            macro_rules! cfg_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    cfg_macro!(@impl $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_cfg_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___cfg_macro {
+                () => {};
+            }
+        )
+    }
+
+    make_test! { [mode_never]
+        input (
+            #[clean_docs(mode = "never")]
+            #[macro_export]
+            macro_rules! mode_never_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    mode_never_macro!(@impl $e)
+                };
+            }
+        )
+
+        expect (
+            #[macro_export]
+            macro_rules! mode_never_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    mode_never_macro!(@impl $e)
+                };
+            }
+        )
+    }
+
+    make_test! { [mode_cfg]
+        input (
+            #[clean_docs(mode = "cfg")]
+            #[macro_export]
+            macro_rules! mode_cfg_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    mode_cfg_macro!(@impl $e)
+                };
+            }
         )
+
+        expect (
+            #[cfg(doc)]
+            #[macro_export]
+            macro_rules! mode_cfg_macro {
+                ($e:expr) => {
+                    $crate::__mode_cfg_macro!(@impl $e)
+                };
+            }
+
+            #[cfg(doc)]
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __mode_cfg_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+            }
+
+            #[cfg(doc)]
+            #[allow(unused_macros)]
+            macro_rules! mode_cfg_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    mode_cfg_macro!(@impl $e)
+                };
+            }
+
+            #[cfg(not(doc))]
+            #[macro_export]
+            macro_rules! mode_cfg_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    mode_cfg_macro!(@impl $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_mode_cfg_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___mode_cfg_macro {
+                () => {};
+            }
+        )
+    }
+
+    make_test! { [invalid_mode]
+        input (
+            #[clean_docs(mode = "sometimes")]
+            macro_rules! invalid_mode_macro {
+                ($e:expr) => {
+                    format!("{}", $e)
+                };
+            }
+        )
+
+        expect (
+            compile_error! {
+                "invalid mode `sometimes`, expected one of `doc`, `always`, `never` or `cfg`"
+            }
+        )
+    }
+
+    make_test! { [module]
+        input (
+            #[clean_docs(impl = "^internal", always = true)]
+            mod module {
+                #[macro_export]
+                macro_rules! module_macro {
+                    (^internal $e:expr) => {
+                        format!("{}", $e)
+                    };
+                    ($e:expr) => {
+                        module_macro!(^internal $e)
+                    };
+                }
+
+                #[clean_docs(internal = "_module_intern_mac")]
+                #[macro_export]
+                macro_rules! module_custom_internal_macro {
+                    (^internal $e:expr) => {
+                        format!("{}", $e)
+                    };
+                    ($e:expr) => {
+                        module_custom_internal_macro!(^internal $e)
+                    };
+                }
+
+                fn unrelated() {}
+            }
+        )
+
+        expect (
+            mod module {
+                #[macro_export]
+                macro_rules! module_macro {
+                    ($e:expr) => {
+                        $crate::__module_macro!(^internal $e)
+                    };
+                }
+
+                #[doc(hidden)]
+                #[macro_export]
+                macro_rules! __module_macro {
+                    (^internal $e:expr) => {
+                        format!("{}", $e)
+                    };
+                }
+
+                #[allow(unused_macros)]
+                macro_rules! module_macro {
+                    (^internal $e:expr) => {
+                        format!("{}", $e)
+                    };
+                    ($e:expr) => {
+                        module_macro!(^internal $e)
+                    };
+                }
+
+                #[doc(hidden)]
+                #[macro_export]
+                macro_rules! __clean_macro_docs_name_module_macro {
+                    () => {};
+                }
+
+                #[doc(hidden)]
+                #[macro_export]
+                macro_rules! __clean_macro_docs_name___module_macro {
+                    () => {};
+                }
+
+                #[macro_export]
+                macro_rules! module_custom_internal_macro {
+                    ($e:expr) => {
+                        $crate::_module_intern_mac!(^internal $e)
+                    };
+                }
+
+                #[doc(hidden)]
+                #[macro_export]
+                macro_rules! _module_intern_mac {
+                    (^internal $e:expr) => {
+                        format!("{}", $e)
+                    };
+                }
+
+                #[allow(unused_macros)]
+                macro_rules! module_custom_internal_macro {
+                    (^internal $e:expr) => {
+                        format!("{}", $e)
+                    };
+                    ($e:expr) => {
+                        module_custom_internal_macro!(^internal $e)
+                    };
+                }
+
+                #[doc(hidden)]
+                #[macro_export]
+                macro_rules! __clean_macro_docs_name_module_custom_internal_macro {
+                    () => {};
+                }
+
+                #[doc(hidden)]
+                #[macro_export]
+                macro_rules! __clean_macro_docs_name__module_intern_mac {
+                    () => {};
+                }
+
+                fn unrelated() {}
+            }
+        )
+    }
+
+    make_test! { [module_internal]
+        input (
+            #[clean_docs(internal = "_intern_mac")]
+            mod module_internal {}
+        )
+
+        expect (
+            compile_error!("`internal` must be a template containing `{name}` or `{hash}` when set for a whole module");
+        )
+    }
+
+    make_test! { [internal_template]
+        input (
+            #[clean_docs(internal = "_{name}_impl", always = true)]
+            #[macro_export]
+            macro_rules! internal_template_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    internal_template_macro!(@impl $e)
+                };
+            }
+        )
+
+        expect (
+            #[macro_export]
+            macro_rules! internal_template_macro {
+                ($e:expr) => {
+                    $crate::_internal_template_macro_impl!(@impl $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! _internal_template_macro_impl {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+            }
+
+            #[allow(unused_macros)]
+            macro_rules! internal_template_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    internal_template_macro!(@impl $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_internal_template_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name__internal_template_macro_impl {
+                () => {};
+            }
+        )
+    }
+
+    make_test! { [raw_ident]
+        input (
+            #[clean_docs(always = true)]
+            #[macro_export]
+            macro_rules! r#match {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    r#match!(@impl $e)
+                };
+            }
+        )
+
+        expect (
+            #[macro_export]
+            macro_rules! r#match {
+                ($e:expr) => {
+                    $crate::__match!(@impl $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __match {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+            }
+
+            #[allow(unused_macros)]
+            macro_rules! r#match {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    r#match!(@impl $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_match {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___match {
+                () => {};
+            }
+        )
+    }
+
+    make_test! { [keyword_template]
+        input (
+            #[clean_docs(internal = "typ{name}", always = true)]
+            #[macro_export]
+            macro_rules! e {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    e!(@impl $e)
+                };
+            }
+        )
+
+        expect (
+            #[macro_export]
+            macro_rules! e {
+                ($e:expr) => {
+                    $crate::r#type!(@impl $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! r#type {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+            }
+
+            #[allow(unused_macros)]
+            macro_rules! e {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    e!(@impl $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_e {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_type {
+                () => {};
+            }
+        )
+    }
+
+    make_test! { [invalid_template]
+        input (
+            #[clean_docs(internal = "__{nmae}", always = true)]
+            #[macro_export]
+            macro_rules! invalid_template_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    invalid_template_macro!(@impl $e)
+                };
+            }
+        )
+
+        expect (
+            compile_error!("unknown placeholder `{nmae}`, expected `{name}`, `{crate}` or `{hash}`");
+        )
+    }
+
+    make_test! { [not_exported]
+        input (
+            #[clean_docs(always = true)]
+            macro_rules! not_exported_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    not_exported_macro!(@impl $e)
+                };
+            }
+        )
+
+        expect (
+            macro_rules! not_exported_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    not_exported_macro!(@impl $e)
+                };
+            }
+        )
+    }
+
+    make_test! { [not_exported_path]
+        input (
+            #[clean_docs(cfg = "doc")]
+            macro_rules! not_exported_path_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    $crate::module::not_exported_path_macro!(@impl $e)
+                };
+            }
+        )
+
+        expect (
+            macro_rules! not_exported_path_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    $crate::module::not_exported_path_macro!(@impl $e)
+                };
+            }
+        )
+    }
+
+    make_test! { [crate_path]
+        input (
+            #[clean_docs(crate_path = "::facade::__macros", always = true)]
+            #[macro_export]
+            macro_rules! crate_path_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    crate_path_macro!(@impl $e)
+                };
+            }
+        )
+
+        expect (
+            #[macro_export]
+            macro_rules! crate_path_macro {
+                ($e:expr) => {
+                    ::facade::__macros::__crate_path_macro!(@impl $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __crate_path_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+            }
+
+            #[allow(unused_macros)]
+            macro_rules! crate_path_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    crate_path_macro!(@impl $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_crate_path_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___crate_path_macro {
+                () => {};
+            }
+        )
+    }
+
+    make_test! { [invalid_crate_path]
+        input (
+            #[clean_docs(crate_path = "facade::__macros", always = true)]
+            #[macro_export]
+            macro_rules! invalid_crate_path_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    invalid_crate_path_macro!(@impl $e)
+                };
+            }
+        )
+
+        expect (
+            compile_error! { "expected an absolute path, like `::foo::__macros`" }
+        )
+    }
+
+    make_test! { [local_reexport]
+        input (
+            #[clean_docs(local = "reexport", always = true)]
+            #[macro_export]
+            macro_rules! local_reexport_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    local_reexport_macro!(@impl $e)
+                };
+            }
+        )
+
+        expect (
+            #[macro_export]
+            macro_rules! local_reexport_macro {
+                ($e:expr) => {
+                    $crate::__local_reexport_macro!(@impl $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __local_reexport_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+            }
+
+            #[allow(unused_macros)]
+            macro_rules! local_reexport_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    local_reexport_macro!(@impl $e)
+                };
+            }
+            #[allow(unused_imports)]
+            pub(crate) use local_reexport_macro;
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_local_reexport_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___local_reexport_macro {
+                () => {};
+            }
+        )
+    }
+
+    make_test! { [local_none]
+        input (
+            #[clean_docs(local = "none", always = true)]
+            #[macro_export]
+            macro_rules! local_none_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    local_none_macro!(@impl $e)
+                };
+            }
+        )
+
+        expect (
+            #[macro_export]
+            macro_rules! local_none_macro {
+                ($e:expr) => {
+                    $crate::__local_none_macro!(@impl $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __local_none_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_local_none_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___local_none_macro {
+                () => {};
+            }
+        )
+    }
+
+    make_test! { [no_qualify_self]
+        input (
+            #[clean_docs(qualify_self = false, always = true)]
+            #[macro_export]
+            macro_rules! no_qualify_self_macro {
+                (@impl $t:tt) => {
+                    no_qualify_self_macro!($t)
+                };
+                ([$t:tt]) => {
+                    no_qualify_self_macro!(@impl $t)
+                };
+                ($t:tt) => {
+                    $t
+                };
+            }
+        )
+
+        expect (
+            #[macro_export]
+            macro_rules! no_qualify_self_macro {
+                ([$t:tt]) => {
+                    $crate::__no_qualify_self_macro!(@impl $t)
+                };
+                ($t:tt) => {
+                    $t
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __no_qualify_self_macro {
+                (@impl $t:tt) => {
+                    no_qualify_self_macro!($t)
+                };
+            }
+
+            #[allow(unused_macros)]
+            macro_rules! no_qualify_self_macro {
+                (@impl $t:tt) => {
+                    no_qualify_self_macro!($t)
+                };
+                ([$t:tt]) => {
+                    no_qualify_self_macro!(@impl $t)
+                };
+                ($t:tt) => {
+                    $t
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_no_qualify_self_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___no_qualify_self_macro {
+                () => {};
+            }
+        )
+    }
+
+    make_test! { [qualify_self]
+        input (
+            #[clean_docs(always = true)]
+            #[macro_export]
+            macro_rules! qualify_self_macro {
+                (@impl $t:tt) => {
+                    qualify_self_macro!($t)
+                };
+                ([$t:tt]) => {
+                    qualify_self_macro!(@impl $t)
+                };
+                ($t:tt) => {
+                    $t
+                };
+            }
+        )
+
+        expect (
+            #[macro_export]
+            macro_rules! qualify_self_macro {
+                ([$t:tt]) => {
+                    $crate::__qualify_self_macro!(@impl $t)
+                };
+                ($t:tt) => {
+                    $t
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __qualify_self_macro {
+                (@impl $t:tt) => {
+                    $crate::qualify_self_macro!($t)
+                };
+            }
+
+            #[allow(unused_macros)]
+            macro_rules! qualify_self_macro {
+                (@impl $t:tt) => {
+                    qualify_self_macro!($t)
+                };
+                ([$t:tt]) => {
+                    qualify_self_macro!(@impl $t)
+                };
+                ($t:tt) => {
+                    $t
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_qualify_self_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___qualify_self_macro {
+                () => {};
+            }
+        )
+    }
+
+    make_test! { [qualify]
+        input (
+            #[clean_docs(qualify = [helper_a, helper_b], always = true)]
+            #[macro_export]
+            macro_rules! qualify_macro {
+                (@impl $e:expr) => {
+                    helper_a!(helper_b!($e))
+                };
+                ($e:expr) => {
+                    (qualify_macro!(@impl $e), $crate::helper_b!($e), other!($e))
+                };
+            }
+        )
+
+        expect (
+            #[macro_export]
+            macro_rules! qualify_macro {
+                ($e:expr) => {
+                    ($crate::__qualify_macro!(@impl $e), $crate::helper_b!($e), other!($e))
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __qualify_macro {
+                (@impl $e:expr) => {
+                    $crate::helper_a!($crate::helper_b!($e))
+                };
+            }
+
+            #[allow(unused_macros)]
+            macro_rules! qualify_macro {
+                (@impl $e:expr) => {
+                    helper_a!(helper_b!($e))
+                };
+                ($e:expr) => {
+                    (qualify_macro!(@impl $e), $crate::helper_b!($e), other!($e))
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_qualify_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___qualify_macro {
+                () => {};
+            }
+        )
+    }
+
+    make_test! { [qualify_module]
+        input (
+            #[clean_docs(always = true)]
+            mod qualify_module {
+                #[macro_export]
+                macro_rules! qualify_module_macro {
+                    (@impl $e:expr) => {
+                        qualify_module_helper!($e)
+                    };
+                    ($e:expr) => {
+                        qualify_module_macro!(@impl $e)
+                    };
+                }
+
+                mod inner {
+                    #[macro_export]
+                    macro_rules! qualify_module_helper {
+                        ($e:expr) => {
+                            $e
+                        };
+                    }
+                }
+            }
+        )
+
+        expect (
+            mod qualify_module {
+                #[macro_export]
+                macro_rules! qualify_module_macro {
+                    ($e:expr) => {
+                        $crate::__qualify_module_macro!(@impl $e)
+                    };
+                }
+
+                #[doc(hidden)]
+                #[macro_export]
+                macro_rules! __qualify_module_macro {
+                    (@impl $e:expr) => {
+                        $crate::qualify_module_helper!($e)
+                    };
+                }
+
+                #[allow(unused_macros)]
+                macro_rules! qualify_module_macro {
+                    (@impl $e:expr) => {
+                        qualify_module_helper!($e)
+                    };
+                    ($e:expr) => {
+                        qualify_module_macro!(@impl $e)
+                    };
+                }
+
+                #[doc(hidden)]
+                #[macro_export]
+                macro_rules! __clean_macro_docs_name_qualify_module_macro {
+                    () => {};
+                }
+
+                #[doc(hidden)]
+                #[macro_export]
+                macro_rules! __clean_macro_docs_name___qualify_module_macro {
+                    () => {};
+                }
+
+                mod inner {
+                    #[macro_export]
+                    macro_rules! qualify_module_helper {
+                        ($e:expr) => {
+                            $e
+                        };
+                    }
+
+                    #[doc(hidden)]
+                    #[macro_export]
+                    macro_rules! __clean_macro_docs_name_qualify_module_helper {
+                        () => {};
+                    }
+                }
+            }
+        )
+    }
+
+    make_test! { [invalid_qualify]
+        input (
+            #[clean_docs(qualify = "helper", always = true)]
+            #[macro_export]
+            macro_rules! invalid_qualify_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    invalid_qualify_macro!(@impl $e)
+                };
+            }
+        )
+
+        expect (
+            compile_error! { "expected `[...]`" }
+        )
+    }
+
+    make_test! { [local_inner_macros]
+        input (
+            #[clean_docs(local_inner_macros, qualify = [helper], always = true)]
+            #[macro_export]
+            macro_rules! local_inner_macros_macro {
+                (@impl $e:expr) => {
+                    helper!($e)
+                };
+                ($e:expr) => {
+                    local_inner_macros_macro!(@impl $e)
+                };
+                [$e:expr] => {
+                    local_inner_macros_macro!($e)
+                };
+            }
+        )
+
+        expect (
+            #[macro_export(local_inner_macros)]
+            macro_rules! local_inner_macros_macro {
+                ($e:expr) => {
+                    __local_inner_macros_macro!(@impl $e)
+                };
+                [$e:expr] => {
+                    local_inner_macros_macro!($e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export(local_inner_macros)]
+            macro_rules! __local_inner_macros_macro {
+                (@impl $e:expr) => {
+                    helper!($e)
+                };
+            }
+
+            #[allow(unused_macros)]
+            macro_rules! local_inner_macros_macro {
+                (@impl $e:expr) => {
+                    helper!($e)
+                };
+                ($e:expr) => {
+                    local_inner_macros_macro!(@impl $e)
+                };
+                [$e:expr] => {
+                    local_inner_macros_macro!($e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_local_inner_macros_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___local_inner_macros_macro {
+                () => {};
+            }
+        )
+    }
+
+    make_test! { [split]
+        input (
+            #[clean_docs(split, always = true)]
+            #[macro_export]
+            macro_rules! split_macro {
+                (@parse $e:expr) => {
+                    split_macro!(@emit $e)
+                };
+                (@emit $e:expr) => {
+                    format!("{}", $e)
+                };
+                (@ $step:ident $e:expr) => {
+                    split_macro!(@emit $e)
+                };
+                ($e:expr) => {
+                    split_macro!(@parse $e)
+                };
+            }
+        )
+
+        expect (
+            #[macro_export]
+            macro_rules! split_macro {
+                ($e:expr) => {
+                    $crate::__split_macro_parse!(@parse $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __split_macro {
+                (@parse $e:expr) => {
+                    $crate::__split_macro_emit!(@emit $e)
+                };
+                (@emit $e:expr) => {
+                    format!("{}", $e)
+                };
+                (@ $step:ident $e:expr) => {
+                    $crate::__split_macro_emit!(@emit $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __split_macro_parse {
+                (@parse $e:expr) => {
+                    $crate::__split_macro_emit!(@emit $e)
+                };
+                (@ $step:ident $e:expr) => {
+                    $crate::__split_macro_emit!(@emit $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __split_macro_emit {
+                (@emit $e:expr) => {
+                    format!("{}", $e)
+                };
+                (@ $step:ident $e:expr) => {
+                    $crate::__split_macro_emit!(@emit $e)
+                };
+            }
+
+            #[allow(unused_macros)]
+            macro_rules! split_macro {
+                (@parse $e:expr) => {
+                    split_macro!(@emit $e)
+                };
+                (@emit $e:expr) => {
+                    format!("{}", $e)
+                };
+                (@ $step:ident $e:expr) => {
+                    split_macro!(@emit $e)
+                };
+                ($e:expr) => {
+                    split_macro!(@parse $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_split_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___split_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___split_macro_parse {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___split_macro_emit {
+                () => {};
+            }
+        )
+    }
+
+    make_test! { [calls]
+        input (
+            #[clean_docs(calls(calls_b, calls_c = "__calls_c_impl"), always = true)]
+            #[macro_export]
+            macro_rules! calls_a {
+                (@impl $e:expr) => {
+                    (calls_b!(@impl $e), $crate::calls_c!(@impl $e))
+                };
+                ($e:expr) => {
+                    (calls_a!(@impl $e), calls_b!($e))
+                };
+            }
+        )
+
+        expect (
+            #[macro_export]
+            macro_rules! calls_a {
+                ($e:expr) => {
+                    ($crate::__calls_a!(@impl $e), calls_b!($e))
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __calls_a {
+                (@impl $e:expr) => {
+                    ($crate::__calls_b!(@impl $e), $crate::__calls_c_impl!(@impl $e))
+                };
+            }
+
+            #[allow(unused_macros)]
+            macro_rules! calls_a {
+                (@impl $e:expr) => {
+                    (calls_b!(@impl $e), calls_c!(@impl $e))
+                };
+                ($e:expr) => {
+                    (calls_a!(@impl $e), calls_b!($e))
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_calls_a {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___calls_a {
+                () => {};
+            }
+        )
+    }
+
+    make_test! { [calls_hash]
+        input (
+            #[clean_docs(calls(calls_hash_b), internal = "__{hash}", always = true)]
+            #[macro_export]
+            macro_rules! calls_hash_a {
+                (@impl $e:expr) => {
+                    calls_hash_b!(@impl $e)
+                };
+                ($e:expr) => {
+                    calls_hash_a!(@impl $e)
+                };
+            }
+        )
+
+        expect (
+            compile_error!("the internal name of `calls_hash_b` can't contain `{hash}`, give it as `calls(calls_hash_b = \"...\")`");
+        )
+    }
+
+    make_test! { [flag]
+        input (
+            #[clean_docs(always)]
+            #[macro_export]
+            macro_rules! flag_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    flag_macro!(@impl $e)
+                };
+            }
+        )
+
+        expect (
+            #[macro_export]
+            macro_rules! flag_macro {
+                ($e:expr) => {
+                    $crate::__flag_macro!(@impl $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __flag_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+            }
+
+            #[allow(unused_macros)]
+            macro_rules! flag_macro {
+                (@impl $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    flag_macro!(@impl $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_flag_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___flag_macro {
+                () => {};
+            }
+        )
+    }
+
+    make_test! { [invalid_args]
+        input (
+            #[clean_docs(intenal = "__mac", "x", internal, always = "yes", cfg = "doc", cfg = "docsrs")]
+            macro_rules! invalid_args_macro {
+                ($e:expr) => {
+                    format!("{}", $e)
+                };
+            }
+        )
+
+        expect (
+            compile_error! { "unknown argument `intenal`, did you mean `internal`?" }
+            compile_error! { "expected `name = value` or `name`" }
+            compile_error! { "expected `name = \"...\"`" }
+            compile_error! { "expected `true` or `false`" }
+            compile_error! { "duplicate argument `cfg`" }
+        )
+    }
+
+    make_test! { [conflicting_args]
+        input (
+            #[clean_docs(always, mode = "never")]
+            macro_rules! conflicting_args_macro {
+                ($e:expr) => {
+                    format!("{}", $e)
+                };
+            }
+        )
+
+        expect (
+            compile_error! { "conflicting arguments `always` and `mode`" }
+        )
+    }
+
+    make_test! { [multiple_markers]
+        input (
+            #[clean_docs(impl = ["@", "^internal"], impl = "__priv", always = true)]
+            #[macro_export]
+            macro_rules! multiple_markers_macro {
+                (@impl $e:expr) => {
+                    multiple_markers_macro!(^internal $e)
+                };
+                (^internal $e:expr) => {
+                    multiple_markers_macro!(__priv $e)
+                };
+                (__priv $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    multiple_markers_macro!(@impl $e)
+                };
+            }
+        )
+
+        expect (
+            #[macro_export]
+            macro_rules! multiple_markers_macro {
+                ($e:expr) => {
+                    $crate::__multiple_markers_macro!(@impl $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __multiple_markers_macro {
+                (@impl $e:expr) => {
+                    $crate::__multiple_markers_macro!(^internal $e)
+                };
+                (^internal $e:expr) => {
+                    $crate::__multiple_markers_macro!(__priv $e)
+                };
+                (__priv $e:expr) => {
+                    format!("{}", $e)
+                };
+            }
+
+            #[allow(unused_macros)]
+            macro_rules! multiple_markers_macro {
+                (@impl $e:expr) => {
+                    multiple_markers_macro!(^internal $e)
+                };
+                (^internal $e:expr) => {
+                    multiple_markers_macro!(__priv $e)
+                };
+                (__priv $e:expr) => {
+                    format!("{}", $e)
+                };
+                ($e:expr) => {
+                    multiple_markers_macro!(@impl $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_multiple_markers_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___multiple_markers_macro {
+                () => {};
+            }
+        )
+    }
+
+    make_test! { [exact_marker]
+        input (
+            #[clean_docs(impl = "@i", always = true)]
+            #[macro_export]
+            macro_rules! exact_marker_macro {
+                (@i $e:expr) => {
+                    format!("{}", $e)
+                };
+                (@impl $e:expr) => {
+                    exact_marker_macro!(@i $e)
+                };
+                ($e:expr) => {
+                    exact_marker_macro!(@impl $e)
+                };
+            }
+        )
+
+        expect (
+            #[macro_export]
+            macro_rules! exact_marker_macro {
+                (@impl $e:expr) => {
+                    $crate::__exact_marker_macro!(@i $e)
+                };
+                ($e:expr) => {
+                    $crate::exact_marker_macro!(@impl $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __exact_marker_macro {
+                (@i $e:expr) => {
+                    format!("{}", $e)
+                };
+            }
+
+            #[allow(unused_macros)]
+            macro_rules! exact_marker_macro {
+                (@i $e:expr) => {
+                    format!("{}", $e)
+                };
+                (@impl $e:expr) => {
+                    exact_marker_macro!(@i $e)
+                };
+                ($e:expr) => {
+                    exact_marker_macro!(@impl $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_exact_marker_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___exact_marker_macro {
+                () => {};
+            }
+        )
+    }
+
+    make_test! { [marker_kinds]
+        input (
+            #[clean_docs(impl(suffix = "@end", wrapped = "internal", literal = "__impl"), always)]
+            #[macro_export]
+            macro_rules! marker_kinds_macro {
+                ([internal] $t:tt) => {
+                    marker_kinds_macro!($t @end)
+                };
+                ($t:tt @end) => {
+                    marker_kinds_macro!("__impl" $t)
+                };
+                ("__impl" $t:tt) => {
+                    format!("{}", $t)
+                };
+                ($t:tt) => {
+                    marker_kinds_macro!([internal] $t)
+                };
+            }
+        )
+
+        expect (
+            #[macro_export]
+            macro_rules! marker_kinds_macro {
+                ($t:tt) => {
+                    $crate::__marker_kinds_macro!([internal] $t)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __marker_kinds_macro {
+                ([internal] $t:tt) => {
+                    $crate::__marker_kinds_macro!($t @end)
+                };
+                ($t:tt @end) => {
+                    $crate::__marker_kinds_macro!("__impl" $t)
+                };
+                ("__impl" $t:tt) => {
+                    format!("{}", $t)
+                };
+            }
+
+            #[allow(unused_macros)]
+            macro_rules! marker_kinds_macro {
+                ([internal] $t:tt) => {
+                    marker_kinds_macro!($t @end)
+                };
+                ($t:tt @end) => {
+                    marker_kinds_macro!("__impl" $t)
+                };
+                ("__impl" $t:tt) => {
+                    format!("{}", $t)
+                };
+                ($t:tt) => {
+                    marker_kinds_macro!([internal] $t)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_marker_kinds_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___marker_kinds_macro {
+                () => {};
+            }
+        )
+    }
+
+    make_test! { [invalid_marker_kind]
+        input (
+            #[clean_docs(impl(sufix = "@end"))]
+            macro_rules! invalid_marker_kind_macro {
+                ($t:tt) => {
+                    format!("{}", $t)
+                };
+            }
+        )
+
+        expect (
+            compile_error! { "unknown marker kind `sufix`, did you mean `suffix`?" }
+        )
+    }
+
+    make_test! { [annotated]
+        input (
+            #[clean_docs(always = true)]
+            #[macro_export]
+            macro_rules! annotated_macro {
+                #[internal]
+                (parse $e:expr) => {
+                    format!("{}", $e)
+                };
+                #[public]
+                (@verbose $e:expr) => {
+                    format!("verbose: {}", annotated_macro!(@impl $e))
+                };
+                (@impl $e:expr) => {
+                    annotated_macro!(parse $e)
+                };
+                ($e:expr) => {
+                    annotated_macro!(@verbose $e)
+                };
+            }
+        )
+
+        expect (
+            #[macro_export]
+            macro_rules! annotated_macro {
+                (@verbose $e:expr) => {
+                    format!("verbose: {}", $crate::__annotated_macro!(@impl $e))
+                };
+                ($e:expr) => {
+                    $crate::annotated_macro!(@verbose $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __annotated_macro {
+                (parse $e:expr) => {
+                    format!("{}", $e)
+                };
+                (@impl $e:expr) => {
+                    $crate::__annotated_macro!(parse $e)
+                };
+            }
+
+            #[allow(unused_macros)]
+            macro_rules! annotated_macro {
+                (parse $e:expr) => {
+                    format!("{}", $e)
+                };
+                (@verbose $e:expr) => {
+                    format!("verbose: {}", annotated_macro!(@impl $e))
+                };
+                (@impl $e:expr) => {
+                    annotated_macro!(parse $e)
+                };
+                ($e:expr) => {
+                    annotated_macro!(@verbose $e)
+                };
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name_annotated_macro {
+                () => {};
+            }
+
+            #[doc(hidden)]
+            #[macro_export]
+            macro_rules! __clean_macro_docs_name___annotated_macro {
+                () => {};
+            }
+        )
+    }
+}
+
+
+mod annotated {
+    use crate::CleanDocsItem;
+
+    #[test]
+    fn internal_without_literal() {
+        let err = syn::parse_str::<CleanDocsItem>(
+            "#[macro_export] macro_rules! annotated_no_literal { \
+                #[internal] ($a:expr; $b:expr) => { $a + $b }; \
+                ($e:expr) => { annotated_no_literal!($e; 1) }; \
+            }",
+        )
+        .err()
+        .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "an `#[internal]` rule must start with literal tokens, like `(@name ...)`"
+        );
+    }
+
+    #[test]
+    fn public_without_literal() {
+        assert!(syn::parse_str::<CleanDocsItem>(
+            "macro_rules! annotated_public { #[public] ($e:expr) => { $e }; }"
+        )
+        .is_ok());
+    }
+}
+
+mod mode {
+    use crate::mode::{resolve_override, Mode};
+
+    #[test]
+    fn no_override() {
+        assert_eq!(resolve_override(None, false, false, false), Ok(None));
+    }
+
+    #[test]
+    fn features() {
+        assert_eq!(resolve_override(None, false, false, true), Ok(Some(Mode::Always)));
+        assert_eq!(resolve_override(None, false, true, false), Ok(Some(Mode::Cfg)));
+        assert_eq!(resolve_override(None, true, false, false), Ok(Some(Mode::Never)));
+        // `force-never` wins over `force-cfg`, which wins over `force-always`
+        assert_eq!(resolve_override(None, true, true, true), Ok(Some(Mode::Never)));
+        assert_eq!(resolve_override(None, false, true, true), Ok(Some(Mode::Cfg)));
+    }
+
+    #[test]
+    fn env() {
+        for (val, mode) in &[
+            ("doc", Mode::Doc),
+            ("always", Mode::Always),
+            ("never", Mode::Never),
+            ("cfg", Mode::Cfg),
+        ] {
+            assert_eq!(resolve_override(Some(val), false, false, false), Ok(Some(*mode)));
+        }
+        // The environment variable wins over every feature
+        assert_eq!(resolve_override(Some("always"), true, true, false), Ok(Some(Mode::Always)));
+    }
+
+    #[test]
+    fn invalid_env() {
+        assert_eq!(
+            resolve_override(Some("sometimes"), true, false, false),
+            Err("CLEAN_MACRO_DOCS_MODE: invalid mode `sometimes`, expected one of `doc`, `always`, `never` or `cfg`".to_string())
+        );
+    }
+}
+
+mod manifest {
+    use crate::manifest::{parse_manifest_defaults, read_manifest_defaults};
+    use crate::marker::Marker;
+    use crate::mode::Mode;
+
+    fn marks(priv_marker: &Marker, rule: &str) -> bool {
+        priv_marker.matches(&rule.parse().unwrap())
+    }
+
+    #[test]
+    fn defaults() {
+        let defaults = parse_manifest_defaults(
+            r##"
+            [package]
+            name = "foo"
+
+            [package.metadata.clean-macro-docs]
+            mode = "cfg"
+            cfg = "docsrs"
+            impl = "#internal"
+            internal = "__{name}_impl"
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(defaults.mode, Some(Mode::Cfg));
+        assert_eq!(defaults.cfg.unwrap().path().get_ident().unwrap(), "docsrs");
+        let priv_markers = defaults.priv_markers.unwrap();
+        assert_eq!(priv_markers.len(), 1);
+        assert!(marks(&priv_markers[0], "#internal $e:expr"));
+        assert_eq!(defaults.priv_ident.unwrap().value(), "__{name}_impl");
+    }
+
+    #[test]
+    fn multiple_markers() {
+        let defaults = parse_manifest_defaults(
+            r##"
+            [package.metadata.clean-macro-docs]
+            impl = ["@", "#internal", { suffix = "@end", literal = "__impl" }]
+            "##,
+        )
+        .unwrap();
+
+        let priv_markers = defaults.priv_markers.unwrap();
+        assert_eq!(priv_markers.len(), 4);
+        assert!(marks(&priv_markers[0], "@impl $e:expr"));
+        assert!(marks(&priv_markers[1], "#internal $e:expr"));
+        assert!(priv_markers[2..]
+            .iter()
+            .any(|priv_marker| marks(priv_marker, "$e:expr @end")));
+        assert!(priv_markers[2..]
+            .iter()
+            .any(|priv_marker| marks(priv_marker, "\"__impl\" $e:expr")));
+    }
+
+    #[test]
+    fn no_metadata() {
+        let defaults = parse_manifest_defaults(
+            r#"
+            [package]
+            name = "foo"
+            "#,
+        )
+        .unwrap();
+
+        assert!(defaults.mode.is_none());
+        assert!(defaults.priv_markers.is_none());
+    }
+
+    #[test]
+    fn missing_manifest() {
+        let dir = std::env::temp_dir().join("clean-macro-docs-missing-manifest");
+        let defaults = read_manifest_defaults(&dir).unwrap();
+        assert!(defaults.mode.is_none());
+        assert!(defaults.priv_markers.is_none());
+    }
+
+    #[test]
+    fn malformed() {
+        let manifest = |metadata| {
+            parse_manifest_defaults(&format!(
+                "[package.metadata.clean-macro-docs]\n{}",
+                metadata
+            ))
+            .err()
+            .unwrap()
+        };
+
+        assert_eq!(
+            manifest("always = true"),
+            "unknown key `package.metadata.clean-macro-docs.always`"
+        );
+        assert_eq!(
+            manifest("intenral = [\"x\"]"),
+            "unknown key `package.metadata.clean-macro-docs.intenral`, did you mean `internal`?"
+        );
+        assert_eq!(
+            manifest("internal_path = \"__private\""),
+            "unknown key `package.metadata.clean-macro-docs.internal_path`"
+        );
+        assert_eq!(
+            manifest("mode = true"),
+            "`package.metadata.clean-macro-docs.mode`: expected a string"
+        );
+        assert_eq!(
+            manifest("mode = \"sometimes\""),
+            "`package.metadata.clean-macro-docs.mode`: invalid mode `sometimes`, \
+             expected one of `doc`, `always`, `never` or `cfg`"
+        );
+        assert_eq!(
+            manifest("internal = \"__mac\""),
+            "`package.metadata.clean-macro-docs.internal`: expected a template containing `{name}` or `{hash}`"
+        );
+        assert_eq!(
+            manifest("intern = \"__{name}\""),
+            "unknown key `package.metadata.clean-macro-docs.intern`, did you mean `internal`?"
+        );
+    }
+}
+
+mod collision {
+    use super::expand;
+
+    fn mac(name: &str) -> String {
+        format!(
+            "#[macro_export] macro_rules! {} {{ (@impl) => {{}}; () => {{ {}!(@impl) }}; }}",
+            name, name
+        )
+    }
+
+    fn guard(name: &str) -> String {
+        format!(
+            "# [doc (hidden)] # [macro_export] macro_rules ! __clean_macro_docs_name_{} {{ () => {{ }} ; }}",
+            name
+        )
+    }
+
+    #[test]
+    fn same_internal() {
+        // Both define the same guard, which the compiler reports
+        let args = "internal = \"__collision_shared\", always";
+        assert!(expand(args, &mac("collision_a")).contains(&guard("__collision_shared")));
+        assert!(expand(args, &mac("collision_b")).contains(&guard("__collision_shared")));
+    }
+
+    #[test]
+    fn internal_and_exported() {
+        assert!(expand("always", &mac("collision_d")).contains(&guard("__collision_d")));
+        assert!(expand("always", &mac("__collision_d")).contains(&guard("__collision_d")));
+    }
+
+    #[test]
+    fn not_running() {
+        let output = expand("mode = \"doc\"", &mac("collision_e"));
+        assert!(output.contains(&guard("collision_e")));
+        assert!(output.contains(&guard("__collision_e")));
+
+        // `never` leaves the macro as it is
+        let output = expand("mode = \"never\"", &mac("collision_e"));
+        assert!(!output.contains("__clean_macro_docs_name_"));
+    }
+
+    #[test]
+    fn cfg() {
+        let output = expand(
+            "always",
+            "#[cfg(unix)] #[macro_export] macro_rules! collision_f { \
+                (@impl) => {}; () => { collision_f!(@impl) }; \
+            }",
+        );
+        assert!(output.contains(&format!("# [cfg (unix)] {}", guard("__collision_f"))));
+    }
+
+    #[test]
+    fn not_exported() {
+        let output = expand("always", "macro_rules! collision_g { (@impl) => {}; () => {}; }");
+        assert!(!output.contains("__clean_macro_docs_name_"));
+    }
+
+    #[test]
+    fn rename() {
+        let args = "internal = \"__collision_renamed\", collision = \"rename\", always";
+        let renamed = |name| {
+            let output = expand(args, &mac(name));
+            let start = output.find("__collision_renamed_").unwrap();
+            output[start..start + "__collision_renamed_".len() + 16].to_string()
+        };
+        // Each macro gets its own name, even without a collision
+        assert_ne!(renamed("collision_h"), renamed("collision_i"));
+        assert!(!expand(args, &mac("collision_h")).contains(&guard("__collision_renamed")));
+    }
+}
+
+mod internal_path {
+    use super::expand;
+
+    const NOT_MODULE: &str = "compile_error ! { \"`internal_path` can only be set for a whole module, \
+        which shares the hidden module between its macros\" }";
+
+    #[test]
+    fn single() {
+        // Two of them in one module would create the same hidden module
+        let output = expand(
+            "internal_path = \"__private\", always",
+            "#[macro_export] macro_rules! internal_path_macro { \
+                (@impl $e:expr) => { format!(\"{}\", $e) }; \
+                ($e:expr) => { internal_path_macro!(@impl $e) }; \
+            }",
+        );
+        assert_eq!(output, NOT_MODULE);
+
+        // Macros in a module can only repeat the module's
+        let output = expand(
+            "internal_path = \"macros::__private\", always",
+            "mod macros { \
+                #[clean_docs(internal_path = \"macros::__private\")] \
+                #[macro_export] macro_rules! internal_path_f { (@impl) => {}; () => { internal_path_f!(@impl) }; } \
+                #[clean_docs(internal_path = \"macros::__other\")] \
+                #[macro_export] macro_rules! internal_path_g { (@impl) => {}; () => { internal_path_g!(@impl) }; } \
+            }",
+        );
+        assert!(output.contains("$ crate :: macros :: __private :: internal_path_f ! (@ impl)"));
+        assert!(output.contains(NOT_MODULE));
+        assert!(!output.contains("internal_path_g ! (@ impl)"));
+    }
+
+    #[test]
+    fn module() {
+        let output = expand(
+            "internal_path = \"macros::__private\", always",
+            "mod macros { \
+                #[macro_export] macro_rules! internal_path_a { (@impl) => {}; () => { internal_path_a!(@impl) }; } \
+                #[macro_export] macro_rules! internal_path_b { (@impl) => {}; () => { $crate::internal_path_b!(@impl) }; } \
+            }",
+        );
+        assert!(output.contains("$ crate :: macros :: __private :: internal_path_a ! (@ impl)"));
+        assert!(output.contains("$ crate :: macros :: __private :: internal_path_b ! (@ impl)"));
+        assert_eq!(output.matches("pub mod __private").count(), 1);
+        assert!(output.contains("as internal_path_a ;"));
+        assert!(output.contains("as internal_path_b ;"));
+    }
+
+    #[test]
+    fn nested() {
+        let output = expand(
+            "internal_path = \"macros::__private\", always",
+            "mod macros { \
+                #[macro_export] macro_rules! internal_path_c { (@impl) => {}; () => { internal_path_c!(@impl) }; } \
+                mod nested { \
+                    #[macro_export] macro_rules! internal_path_d { (@impl) => {}; () => { internal_path_d!(@impl) }; } \
+                    mod deeper { \
+                        #[macro_export] macro_rules! internal_path_e { (@impl) => {}; () => { internal_path_e!(@impl) }; } \
+                    } \
+                } \
+            }",
+        );
+        assert!(output.contains("$ crate :: macros :: __private :: internal_path_d ! (@ impl)"));
+        assert!(output.contains("$ crate :: macros :: __private :: internal_path_e ! (@ impl)"));
+        assert_eq!(output.matches("pub mod").count(), 1);
+
+        // Each module on the way re-exports the internal macros below it
+        assert!(output.contains("# [doc (hidden)] pub use __internal_path_d_"));
+        assert!(output.contains("# [doc (hidden)] pub use __internal_path_e_"));
+        assert!(output.contains("# [doc (hidden)] pub use deeper :: __internal_path_e_"));
+        assert!(output.contains("pub use __internal_path_c_"));
+        assert!(output.contains("pub use super :: nested :: __internal_path_d_"));
+        assert!(output.contains("pub use super :: nested :: __internal_path_e_"));
+    }
+
+    #[test]
+    fn nested_own_path() {
+        let output = expand(
+            "internal_path = \"macros::__private\", always",
+            "mod macros { \
+                #[macro_export] macro_rules! internal_path_h { (@impl) => {}; () => { internal_path_h!(@impl) }; } \
+                #[clean_docs(internal_path = \"macros::nested::__private\")] \
+                mod nested { \
+                    #[macro_export] macro_rules! internal_path_i { (@impl) => {}; () => { internal_path_i!(@impl) }; } \
+                } \
+            }",
+        );
+        assert!(output.contains("$ crate :: macros :: nested :: __private :: internal_path_i ! (@ impl)"));
+        assert_eq!(output.matches("pub mod __private").count(), 2);
+        assert!(!output.contains("pub use super :: nested"));
+    }
+
+    #[test]
+    fn invalid() {
+        let mac = "#[macro_export] macro_rules! invalid_internal_path_macro { () => {}; }";
+        for path in &["::__private", "crate::__private", "__private<T>", "not a path"] {
+            assert_eq!(
+                expand(&format!("internal_path = {:?}", path), mac),
+                "compile_error ! { \"expected a module path relative to the crate root, like `macros::__private`\" }"
+            );
+        }
+    }
+}
+
+mod local_inner_macros {
+    use super::expand;
+
+    const MAC: &str = "#[macro_export] macro_rules! local_inner_warning_macro { \
+        (@impl $m:ident $e:expr) => { (format!(\"{}\", $e), $m!($e), ::std::vec![$e]) }; \
+        ($e:expr) => { local_inner_warning_macro!(@impl helper $e) }; \
+    }";
+
+    #[test]
+    fn warnings() {
+        // Only `format!` is called without a path
+        let output = expand("local_inner_macros", MAC);
+        assert_eq!(output.matches("# [deprecated").count(), 1);
+        assert!(output.contains("`format!` is called as `$crate::format!`"));
+        assert!(output.contains("# [macro_export] macro_rules ! local_inner_warning_macro"));
+
+        let output = expand("local_inner_macros, always", MAC);
+        assert_eq!(output.matches("# [deprecated").count(), 1);
+        assert!(output.contains("# [macro_export (local_inner_macros)]"));
+    }
+
+    #[test]
+    fn not_exported() {
+        let output = expand(
+            "local_inner_macros, always",
+            "macro_rules! local_inner_private_macro { \
+                (@impl $e:expr) => { format!(\"{}\", $e) }; \
+                ($e:expr) => { local_inner_private_macro!(@impl $e) }; \
+            }",
+        );
+        assert!(!output.contains("deprecated"));
+        assert!(!output.contains("local_inner_macros"));
+    }
+}
+
+mod qualify {
+    use super::expand;
+
+    #[test]
+    fn self_metavar() {
+        // `$meta!()` invokes the macro passed as `$meta`, even if it has the
+        // same name
+        let output = expand(
+            "always",
+            "#[macro_export] macro_rules! meta { \
+                (@impl $meta:ident) => { $meta!() }; \
+                ($meta:ident) => { meta!(@impl $meta) }; \
+            }",
+        );
+        assert!(output.contains("(@ impl $ meta : ident) => { $ meta ! () }"));
+        assert!(!output.contains("$ $ crate"));
+    }
+
+    #[test]
+    fn other_metavar() {
+        let output = expand(
+            "qualify = [helper], always",
+            "#[macro_export] macro_rules! qualify_meta { \
+                (@impl $helper:ident) => { ($helper!(), helper!()) }; \
+                ($helper:ident) => { qualify_meta!(@impl $helper) }; \
+            }",
+        );
+        assert!(output.contains("($ helper ! () , $ crate :: helper ! ())"));
+        assert!(!output.contains("$ $ crate"));
+    }
+}
+
+mod split {
+    use super::expand;
+
+    #[test]
+    fn groups() {
+        let output = expand(
+            "split, always",
+            "#[macro_export] macro_rules! split_groups { \
+                (@impl[0] $e:expr) => { split_groups!(@impl[1] $e) }; \
+                (@impl[1] $e:expr) => { split_groups!(@impl[2] $e + 1) }; \
+                (@impl[2] $e:expr) => { $e }; \
+                ($e:expr) => { split_groups!(@impl[0] $e) }; \
+            }",
+        );
+        assert!(output.contains(
+            "macro_rules ! __split_groups_impl_0 { \
+             (@ impl [0] $ e : expr) => { $ crate :: __split_groups_impl_1 ! (@ impl [1] $ e) } ; }"
+        ));
+        assert!(output.contains(
+            "macro_rules ! __split_groups_impl_1 { \
+             (@ impl [1] $ e : expr) => { $ crate :: __split_groups_impl_2 ! (@ impl [2] $ e + 1) } ; }"
+        ));
+        assert!(output.contains("macro_rules ! __split_groups_impl_2 { (@ impl [2] $ e : expr) => { $ e } ; }"));
+        // `@impl` alone would have every rule
+        assert!(!output.contains("__split_groups_impl !"));
+    }
+
+    #[test]
+    fn unknown_groups() {
+        let output = expand(
+            "split, always",
+            "#[macro_export] macro_rules! split_unknown { \
+                (@impl[0] $e:expr) => { $e }; \
+                (@impl[$n:tt] $e:expr) => { split_unknown!(@impl[$n] $e) }; \
+                (@emit $e:expr) => { split_unknown!(@impl[0] $e) }; \
+                ($e:expr) => { split_unknown!(@emit $e) }; \
+            }",
+        );
+        // Rules with metavariables in the group are kept for every group
+        assert!(output.contains(
+            "macro_rules ! __split_unknown_impl_0 { \
+             (@ impl [0] $ e : expr) => { $ e } ; \
+             (@ impl [$ n : tt] $ e : expr) => { $ crate :: __split_unknown_impl ! (@ impl [$ n] $ e) } ; }"
+        ));
+        // Invocations whose group isn't known call the macro for `@impl`
+        assert!(output.contains(
+            "macro_rules ! __split_unknown_impl { \
+             (@ impl [0] $ e : expr) => { $ e } ; \
+             (@ impl [$ n : tt] $ e : expr) => { $ crate :: __split_unknown_impl ! (@ impl [$ n] $ e) } ; }"
+        ));
+        assert!(output.contains("$ crate :: __split_unknown_impl_0 ! (@ impl [0] $ e)"));
+    }
+}
+
+mod seal {
+    use super::expand;
+    use crate::naming::seal_ident;
+
+    #[test]
+    fn rules_and_invocations() {
+        let seal = seal_ident().to_string();
+        let output = expand(
+            "seal, calls(sealed_helper), always",
+            "#[macro_export] macro_rules! sealed_macro { \
+                (@impl $e:expr) => { sealed_helper!(@impl sealed_macro!(@inner $e)) }; \
+                (@inner $e:expr) => { $e }; \
+                ($e:expr) => { (sealed_macro!(@impl $e), sealed_macro!([$e])) }; \
+                ([$e:expr]) => { $e }; \
+            }",
+        );
+        // Every internal rule, and every rewritten invocation, starts with the
+        // seal
+        assert!(output.contains(&format!("({} @ impl $ e : expr)", seal)));
+        assert!(output.contains(&format!("({} @ inner $ e : expr)", seal)));
+        assert!(output.contains(&format!(
+            "$ crate :: __sealed_helper ! ({} @ impl $ crate :: __sealed_macro ! ({} @ inner $ e))",
+            seal, seal
+        )));
+        assert!(output.contains(&format!("$ crate :: __sealed_macro ! ({} @ impl $ e)", seal)));
+        // Public rules, and the local copy, aren't sealed
+        assert!(output.contains("$ crate :: sealed_macro ! ([$ e])"));
+        assert_eq!(output.matches(&seal).count(), 5);
+    }
+
+    #[test]
+    fn warn_direct() {
+        let seal = seal_ident().to_string();
+        let output = expand(
+            "warn_direct, always",
+            "#[macro_export] macro_rules! warn_direct_macro { \
+                (@impl $e:expr) => { $e }; \
+                ($e:expr) => { warn_direct_macro!(@impl $e) }; \
+            }",
+        );
+        // Invocations without the seal go to the deprecated copy, which has
+        // the unsealed rules. Sealed ones that match no rule don't.
+        assert!(output.contains(&format!(
+            "macro_rules ! __warn_direct_macro {{ ({0} @ impl $ e : expr) => {{ $ e }} ; \
+            ({0} $ ($ tokens : tt) *) => {{ compile_error ! (concat ! (\"no internal rule of `warn_direct_macro!` expected `\" , \
+            stringify ! ($ ($ tokens) *) , \"`\")) }} ; \
+            ($ ($ tokens : tt) *) => {{ $ crate :: __warn_direct_macro_direct ! ($ ($ tokens) *) }} ; }}",
+            seal
+        )));
+        assert!(output.contains(
+            "# [deprecated (note = \"internal to `warn_direct_macro!`, and may change in any release; \
+            use `warn_direct_macro!` instead\")] \
+            macro_rules ! __warn_direct_macro_direct { (@ impl $ e : expr) => { $ e } ; }"
+        ));
+        assert!(output.contains(&format!("$ crate :: __warn_direct_macro ! ({} @ impl $ e)", seal)));
+
+        // Sealed rules can't be called directly at all
+        let output = expand(
+            "warn_direct, seal, always",
+            "#[macro_export] macro_rules! warn_direct_sealed_macro { \
+                (@impl $e:expr) => { $e }; \
+                ($e:expr) => { warn_direct_sealed_macro!(@impl $e) }; \
+            }",
+        );
+        assert!(!output.contains("deprecated"));
+    }
+}
+
+mod family {
+    use crate::clean_docs_family_impl;
+
+    fn expand(input: &str) -> String {
+        clean_docs_family_impl(syn::parse_str(input).unwrap()).to_string()
+    }
+
+    #[test]
+    fn shared() {
+        let output = expand(
+            "#![clean_docs(always)] \
+            #[internal] macro_rules! __family_shared { \
+                (@fmt $e:expr) => { format!(\"{}\", $e) }; \
+                (@twice $e:expr) => { (family_a!(@fmt $e), __family_shared!(@fmt $e)) }; \
+            } \
+            #[macro_export] macro_rules! family_a { ($e:expr) => { family_a!(@fmt $e) }; } \
+            #[macro_export] macro_rules! family_b { ($e:expr) => { family_b!(@twice family_a!($e)) }; }",
+        );
+        assert_eq!(output.matches("# [macro_export] macro_rules ! __family_shared").count(), 1);
+        assert!(output.contains(
+            "# [macro_export] macro_rules ! family_a { ($ e : expr) => { $ crate :: __family_shared ! (@ fmt $ e) } ; }"
+        ));
+        assert!(output.contains(
+            "# [macro_export] macro_rules ! family_b { ($ e : expr) => { $ crate :: __family_shared ! (@ twice $ crate :: family_a ! ($ e)) } ; }"
+        ));
+        assert!(output.contains(
+            "($ crate :: __family_shared ! (@ fmt $ e) , $ crate :: __family_shared ! (@ fmt $ e))"
+        ));
+    }
+
+    #[test]
+    fn invalid() {
+        let cases = [
+            (
+                "macro_rules! family_invalid { () => {}; }",
+                "expected an `#[internal]` macro with the shared internal rules",
+            ),
+            (
+                "#[internal] macro_rules! __family_invalid { (@impl) => {}; }",
+                "expected at least one public macro",
+            ),
+            (
+                "#[internal] macro_rules! __family_invalid { (@impl) => {}; } \
+                #[internal] macro_rules! __family_invalid_2 { (@impl) => {}; } \
+                macro_rules! family_invalid { () => {}; }",
+                "only one macro of a family can be `#[internal]`",
+            ),
+            (
+                "#[internal] macro_rules! __family_invalid { (@impl) => {}; } \
+                macro_rules! family_invalid { (@own) => {}; () => {}; }",
+                "internal rules of a family go in its `#[internal]` macro",
+            ),
+            (
+                "#[internal] macro_rules! __family_invalid { (@impl) => {}; } \
+                #[macro_export] macro_rules! family_invalid { () => {}; } \
+                macro_rules! family_invalid_2 { () => {}; }",
+                "either every macro of a family is exported, or none is",
+            ),
+            (
+                "#[internal] macro_rules! __family_invalid { (@impl) => {}; } \
+                #[clean_docs(always)] macro_rules! family_invalid { () => {}; }",
+                "arguments are given for the whole family, as `#![clean_docs(...)]`",
+            ),
+            (
+                "#![allow(unused)] \
+                #[internal] macro_rules! __family_invalid { (@impl) => {}; } \
+                macro_rules! family_invalid { () => {}; }",
+                "expected `#![clean_docs(...)]`",
+            ),
+            (
+                "#![clean_docs(internal_path = \"__private\")] \
+                #[internal] macro_rules! __family_invalid { (@impl) => {}; } \
+                #[macro_export] macro_rules! family_invalid { () => {}; }",
+                "`internal_path` can only be set for a whole module, \
+                which shares the hidden module between its macros",
+            ),
+        ];
+        for (input, msg) in &cases {
+            assert_eq!(expand(input), format!("compile_error ! {{ {:?} }}", msg));
+        }
+    }
+}
+
+mod generated {
+    use crate::{clean_docs_impl, CleanDocsItem};
+    use proc_macro2::{Delimiter, Group, TokenStream, TokenTree};
+    use quote::quote;
+
+    fn none_group(tokens: TokenStream) -> TokenTree {
+        TokenTree::Group(Group::new(Delimiter::None, tokens))
+    }
+
+    // Fragments substituted by another macro, like `$body:block` and
+    // `$e:expr`, are wrapped in groups without delimiters
+    #[test]
+    fn fragments() {
+        let body = none_group(quote!({ generated_macro!(@impl 1) }));
+        let expr = none_group(quote!(generated_macro!(@impl 2)));
+        let item: CleanDocsItem = syn::parse2(quote! {
+            #[macro_export]
+            macro_rules! generated_macro {
+                (@impl $e:expr) => { $e };
+                () => #body;
+                ($e:expr) => { #expr };
+            }
+        })
+        .unwrap();
+
+        let output = clean_docs_impl(vec![syn::parse_quote!(always)], item).to_string();
+        assert!(!output.contains("compile_error"));
+        assert_eq!(
+            output
+                .matches("$ crate :: __generated_macro ! (@ impl")
+                .count(),
+            2
+        );
+    }
+}
+
+#[cfg(feature = "decl-macro")]
+mod decl_macro {
+    use super::expand;
+
+    #[test]
+    fn arms() {
+        assert_eq!(
+            expand(
+                "always",
+                "/// Docs\n\
+                pub(crate) macro decl_mac { \
+                    (@impl $e:expr) => { $e }, \
+                    ($e:expr) => { decl_mac!(@impl $e) }, \
+                }",
+            ),
+            "# [doc = \" Docs\"] pub (crate) macro decl_mac { ($ e : expr) => { __decl_mac ! (@ impl $ e) } , } \
+             # [doc (hidden)] pub (crate) macro __decl_mac { (@ impl $ e : expr) => { $ e } , }"
+        );
+    }
+
+    #[test]
+    fn single_arm() {
+        let mac = "pub macro decl_single($e:expr) { decl_single!(@impl $e) }";
+        assert_eq!(
+            expand("always", mac),
+            "pub macro decl_single ($ e : expr) { decl_single ! (@ impl $ e) }"
+        );
+    }
+
+    #[test]
+    fn module() {
+        let output = expand(
+            "always",
+            "mod decl_module { \
+                pub macro decl_module_mac { (@impl) => {}, () => { decl_module_mac!(@impl) } } \
+            }",
+        );
+        assert!(output.contains("pub macro __decl_module_mac { (@ impl) => { } }"));
+        assert!(output.contains("pub macro decl_module_mac { () => { __decl_module_mac ! (@ impl) } }"));
+    }
+
+    #[test]
+    fn unsupported_args() {
+        let mac = "pub macro decl_args { (@impl) => {}, () => { decl_args!(@impl) } }";
+        assert_eq!(
+            expand("seal, always, internal_path = \"__private\"", mac),
+            "compile_error ! { \"`seal` isn't supported for `macro` items\" } \
+             compile_error ! { \"`internal_path` isn't supported for `macro` items\" }"
+        );
+
+        // Arguments for the whole module also apply to its `macro_rules!` macros
+        let output = expand(
+            "seal, always",
+            &format!("mod decl_args_module {{ #[clean_docs(split)] {} }}", mac),
+        );
+        assert!(output.contains("compile_error ! { \"`split` isn't supported for `macro` items\" }"));
+        assert!(!output.contains("`seal`"));
     }
 }
@@ -0,0 +1,18 @@
+extern crate proc_macro2;
+
+use proc_macro2::{Span, TokenStream};
+use quote::quote_spanned;
+
+// A warning at `span`. Procedural macros can't emit warnings on stable, so
+// this is the use of a deprecated constant, which the `deprecated` lint
+// reports with `msg` as its note.
+pub fn warning(span: Span, msg: &str) -> TokenStream {
+    quote_spanned! {span=>
+        const _: () = {
+            #[deprecated(note = #msg)]
+            #[allow(non_upper_case_globals)]
+            const clean_macro_docs_warning: () = ();
+            clean_macro_docs_warning
+        };
+    }
+}
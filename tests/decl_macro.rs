@@ -0,0 +1,8 @@
+// Run with `cargo +nightly test --features decl-macro`. The tests are in their
+// own module, since stable Rust warns about `macro` items even when they're
+// configured out.
+#![cfg(feature = "decl-macro")]
+#![feature(decl_macro)]
+
+#[path = "decl_macro/tests.rs"]
+mod tests;
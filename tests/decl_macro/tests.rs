@@ -0,0 +1,45 @@
+use clean_macro_docs::clean_docs;
+
+mod macros {
+    use clean_macro_docs::clean_docs;
+
+    #[clean_docs(always = true)]
+    pub macro decl_macro {
+        (@impl $e:expr) => {
+            format!("{}", $e)
+        },
+        ($e:expr) => {
+            decl_macro!(@impl $e)
+        },
+    }
+
+    #[clean_docs(calls(decl_macro), always = true)]
+    pub macro decl_calls_macro {
+        (@impl $e:expr) => {
+            decl_macro!(@impl $e)
+        },
+        ($e:expr) => {
+            decl_calls_macro!(@impl $e)
+        },
+    }
+}
+
+#[clean_docs(always = true)]
+pub macro decl_single_macro($e:expr) {
+    format!("{}", $e)
+}
+
+#[test]
+fn decl_macro() {
+    assert_eq!(macros::decl_macro!(54321), "54321");
+}
+
+#[test]
+fn calls() {
+    assert_eq!(macros::decl_calls_macro!(54321), "54321");
+}
+
+#[test]
+fn single_arm() {
+    assert_eq!(decl_single_macro!(54321), "54321");
+}
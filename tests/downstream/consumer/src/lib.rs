@@ -0,0 +1,105 @@
+//! Calls the macros of `downstream-macros` by path, without importing
+//! anything, as another crate would.
+
+#![cfg(test)]
+// Only direct calls to internal macros may warn, and they must
+#![deny(deprecated, unfulfilled_lint_expectations)]
+
+#[test]
+fn simple() {
+    assert_eq!(downstream_macros::simple_macro!(54321), "54321");
+}
+
+#[test]
+fn qualify_self() {
+    assert_eq!(downstream_macros::back_and_forth_macro!(1, 54321), "54321");
+}
+
+#[test]
+fn qualify() {
+    assert_eq!(downstream_macros::qualify_macro!(54321), "<54321>");
+}
+
+#[test]
+fn internal_path() {
+    assert_eq!(downstream_macros::internal_path_macro!(54321), "54321");
+    assert_eq!(
+        downstream_macros::internal_path_other_macro!(54321),
+        "other: 54321"
+    );
+    assert_eq!(
+        downstream_macros::internal_path_nested_macro!(54321),
+        "nested: 54321"
+    );
+}
+
+mod local_inner_macros {
+    // Calls without a path in the macro resolve at its crate's root, so it
+    // works when imported alone
+    use downstream_macros::local_inner_macros_macro;
+
+    #[test]
+    fn local_inner_macros() {
+        assert_eq!(local_inner_macros_macro!(54321), "<54321>");
+    }
+}
+
+#[test]
+fn split() {
+    assert_eq!(downstream_macros::split_macro!(5), "51");
+}
+
+#[test]
+fn seal() {
+    assert_eq!(downstream_macros::sealed_macro!(54321), "[54321]");
+}
+
+#[test]
+fn warn_direct() {
+    assert_eq!(downstream_macros::warn_direct_macro!(54321), "(54321)");
+}
+
+#[test]
+#[expect(deprecated)]
+fn warn_direct_call() {
+    assert_eq!(
+        downstream_macros::__warn_direct_macro!(@impl 54321),
+        "(54321)"
+    );
+}
+
+#[test]
+fn calls() {
+    assert_eq!(downstream_macros::calls_a_macro!(54321), "b: 54321");
+}
+
+#[test]
+fn annotated() {
+    assert_eq!(downstream_macros::annotated_macro!(54321), "verbose: 54321");
+    assert_eq!(
+        downstream_macros::annotated_macro!(@verbose 54321),
+        "verbose: 54321"
+    );
+}
+
+#[test]
+fn rename() {
+    assert_eq!(downstream_macros::rename_a_macro!(54321), "a: 54321");
+    assert_eq!(downstream_macros::rename_b_macro!(54321), "b: 54321");
+}
+
+#[test]
+fn module() {
+    assert_eq!(downstream_macros::module_a_macro!(54321), "module: 54321");
+}
+
+#[test]
+fn family() {
+    assert_eq!(downstream_macros::family_a_macro!(54321), "54321");
+    assert_eq!(downstream_macros::family_b_macro!(54321), "5432154321");
+}
+
+#[test]
+fn generated() {
+    assert_eq!(downstream_macros::generated_macro!(54321), "54321!");
+}
@@ -0,0 +1,265 @@
+//! Exported macros for `downstream-consumer`, which calls them from another
+//! crate, where only the cleaned versions are reachable.
+
+use clean_macro_docs::clean_docs;
+
+#[clean_docs]
+#[macro_export]
+macro_rules! simple_macro {
+    (@impl $e:expr) => {
+        format!("{}", $e)
+    };
+    ($e:expr) => {
+        simple_macro!(@impl $e)
+    };
+}
+
+// Internal rules calling public ones, without a path
+#[clean_docs]
+#[macro_export]
+macro_rules! back_and_forth_macro {
+    (@impl 0, $e:expr) => {
+        format!("{}", $e)
+    };
+    (@impl $n:tt, $e:expr) => {
+        back_and_forth_macro!(0, $e)
+    };
+    ($n:tt, $e:expr) => {
+        back_and_forth_macro!(@impl $n, $e)
+    };
+}
+
+#[macro_export]
+macro_rules! qualify_helper {
+    ($e:expr) => {
+        format!("<{}>", $e)
+    };
+}
+
+#[clean_docs(qualify = [qualify_helper])]
+#[macro_export]
+macro_rules! qualify_macro {
+    (@impl $e:expr) => {
+        qualify_helper!($e)
+    };
+    ($e:expr) => {
+        qualify_macro!(@impl $e)
+    };
+}
+
+// Every macro in the module and its nested modules is re-exported from the
+// one hidden module
+#[clean_docs(internal_path = "internal_path::__private")]
+pub mod internal_path {
+    #[macro_export]
+    macro_rules! internal_path_macro {
+        (@impl $e:expr) => {
+            format!("{}", $e)
+        };
+        ($e:expr) => {
+            internal_path_macro!(@impl $e)
+        };
+    }
+
+    #[macro_export]
+    macro_rules! internal_path_other_macro {
+        (@impl $e:expr) => {
+            format!("other: {}", $e)
+        };
+        ($e:expr) => {
+            internal_path_other_macro!(@impl $e)
+        };
+    }
+
+    mod nested {
+        pub mod deeper {
+            #[macro_export]
+            macro_rules! internal_path_nested_macro {
+                (@impl $e:expr) => {
+                    format!("nested: {}", $e)
+                };
+                ($e:expr) => {
+                    internal_path_nested_macro!(@impl $e)
+                };
+            }
+        }
+    }
+}
+
+#[clean_docs(local_inner_macros, qualify = [qualify_helper])]
+#[macro_export]
+macro_rules! local_inner_macros_macro {
+    (@impl $e:expr) => {
+        qualify_helper!($e)
+    };
+    ($e:expr) => {
+        local_inner_macros_macro!(@impl $e)
+    };
+}
+
+#[clean_docs(split)]
+#[macro_export]
+macro_rules! split_macro {
+    (@step[0] $e:expr) => {
+        split_macro!(@step[1] $e * 10)
+    };
+    (@step[1] $e:expr) => {
+        split_macro!(@step[2] $e + 1)
+    };
+    (@step[$n:tt] $e:expr) => {
+        split_macro!(@emit $e)
+    };
+    (@emit $e:expr) => {
+        format!("{}", $e)
+    };
+    ($e:expr) => {
+        split_macro!(@step[0] $e)
+    };
+}
+
+#[clean_docs(seal)]
+#[macro_export]
+macro_rules! sealed_macro {
+    (@impl $e:expr) => {
+        format!("[{}]", $e)
+    };
+    ($e:expr) => {
+        sealed_macro!(@impl $e)
+    };
+}
+
+#[clean_docs(warn_direct)]
+#[macro_export]
+macro_rules! warn_direct_macro {
+    (@impl $e:expr) => {
+        format!("({})", $e)
+    };
+    ($e:expr) => {
+        warn_direct_macro!(@impl $e)
+    };
+}
+
+#[clean_docs]
+#[macro_export]
+macro_rules! calls_b_macro {
+    (@impl $e:expr) => {
+        format!("b: {}", $e)
+    };
+    ($e:expr) => {
+        calls_b_macro!(@impl $e)
+    };
+}
+
+#[clean_docs(calls(calls_b_macro))]
+#[macro_export]
+macro_rules! calls_a_macro {
+    (@impl $e:expr) => {
+        calls_b_macro!(@impl $e)
+    };
+    ($e:expr) => {
+        calls_a_macro!(@impl $e)
+    };
+}
+
+#[clean_docs]
+#[macro_export]
+macro_rules! annotated_macro {
+    #[internal]
+    (parse $e:expr) => {
+        format!("{}", $e)
+    };
+    #[public]
+    (@verbose $e:expr) => {
+        format!("verbose: {}", annotated_macro!(parse $e))
+    };
+    ($e:expr) => {
+        annotated_macro!(@verbose $e)
+    };
+}
+
+#[clean_docs(internal = "__renamed", collision = "rename")]
+#[macro_export]
+macro_rules! rename_a_macro {
+    (@impl $e:expr) => {
+        format!("a: {}", $e)
+    };
+    ($e:expr) => {
+        rename_a_macro!(@impl $e)
+    };
+}
+
+#[clean_docs(internal = "__renamed", collision = "rename")]
+#[macro_export]
+macro_rules! rename_b_macro {
+    (@impl $e:expr) => {
+        format!("b: {}", $e)
+    };
+    ($e:expr) => {
+        rename_b_macro!(@impl $e)
+    };
+}
+
+// Exported macros in a module call each other through `$crate::`
+#[clean_docs]
+pub mod module {
+    #[macro_export]
+    macro_rules! module_a_macro {
+        (@impl $e:expr) => {
+            module_b_macro!($e)
+        };
+        ($e:expr) => {
+            module_a_macro!(@impl $e)
+        };
+    }
+
+    #[macro_export]
+    macro_rules! module_b_macro {
+        ($e:expr) => {
+            format!("module: {}", $e)
+        };
+    }
+}
+
+clean_macro_docs::clean_docs_family! {
+    #[internal]
+    macro_rules! __family_macros {
+        (@fmt $e:expr) => {
+            format!("{}", $e)
+        };
+        (@twice $e:expr) => {
+            family_a_macro!(@fmt $e) + &__family_macros!(@fmt $e)
+        };
+    }
+
+    #[macro_export]
+    macro_rules! family_a_macro {
+        ($e:expr) => {
+            family_a_macro!(@fmt $e)
+        };
+    }
+
+    #[macro_export]
+    macro_rules! family_b_macro {
+        ($e:expr) => {
+            family_b_macro!(@twice $e)
+        };
+    }
+}
+
+// `$` is passed as `$d`, since `macro_rules!` can't escape it
+macro_rules! make_generated_macro {
+    ($d:tt $name:ident) => {
+        #[clean_docs]
+        #[macro_export]
+        macro_rules! $name {
+            (@impl $d e:expr) => {
+                format!("{}!", $d e)
+            };
+            ($d e:expr) => {
+                $crate::$name!(@impl $d e)
+            };
+        }
+    };
+}
+
+make_generated_macro!($ generated_macro);
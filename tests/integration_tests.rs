@@ -22,6 +22,288 @@ macro_rules! custom_impl_macro {
     };
 }
 
+#[clean_docs(impl = ["@", "#internal"], always = true)]
+#[macro_export]
+macro_rules! multiple_markers_macro {
+    (@impl $e:expr) => {
+        multiple_markers_macro!(#internal $e)
+    };
+    (#internal $e:expr) => {
+        format!("{}", $e)
+    };
+    ($e:expr) => {
+        multiple_markers_macro!(@impl $e)
+    };
+}
+
+#[clean_docs(impl(suffix = "@end", wrapped = "internal", literal = "__impl"), always)]
+#[macro_export]
+macro_rules! marker_kinds_macro {
+    ([internal] $t:tt) => {
+        marker_kinds_macro!($t @end)
+    };
+    ($t:tt @end) => {
+        marker_kinds_macro!("__impl" $t)
+    };
+    ("__impl" $t:tt) => {
+        format!("{}", $t)
+    };
+    ($t:tt) => {
+        marker_kinds_macro!([internal] $t)
+    };
+}
+
+#[clean_docs(always = true)]
+#[macro_export]
+macro_rules! annotated_macro {
+    #[internal]
+    (parse $e:expr) => {
+        format!("{}", $e)
+    };
+    #[public]
+    (@verbose $e:expr) => {
+        format!("verbose: {}", annotated_macro!(@impl $e))
+    };
+    (@impl $e:expr) => {
+        annotated_macro!(parse $e)
+    };
+    ($e:expr) => {
+        annotated_macro!(@verbose $e)
+    };
+}
+
+#[clean_docs(internal = "__{crate}_{name}_{hash}", always = true)]
+#[macro_export]
+macro_rules! template_macro {
+    (@impl $e:expr) => {
+        format!("{}", $e)
+    };
+    ($e:expr) => {
+        template_macro!(@impl $e)
+    };
+}
+
+#[clean_docs(always = true)]
+#[macro_export]
+macro_rules! r#match {
+    (@impl $e:expr) => {
+        format!("{}", $e)
+    };
+    ($e:expr) => {
+        r#match!(@impl $e)
+    };
+}
+
+#[clean_docs(internal = "__renamed", collision = "rename", always = true)]
+#[macro_export]
+macro_rules! rename_a_macro {
+    (@impl $e:expr) => {
+        format!("a: {}", $e)
+    };
+    ($e:expr) => {
+        rename_a_macro!(@impl $e)
+    };
+}
+
+#[clean_docs(internal = "__renamed", collision = "rename", always = true)]
+#[macro_export]
+macro_rules! rename_b_macro {
+    (@impl $e:expr) => {
+        format!("b: {}", $e)
+    };
+    ($e:expr) => {
+        rename_b_macro!(@impl $e)
+    };
+}
+
+#[clean_docs(internal_path = "internal_path_macros::__private", always = true)]
+mod internal_path_macros {
+    #[macro_export]
+    macro_rules! internal_path_macro {
+        (@impl $e:expr) => {
+            format!("{}", $e)
+        };
+        ($e:expr) => {
+            internal_path_macro!(@impl $e)
+        };
+    }
+
+    #[macro_export]
+    macro_rules! internal_path_other_macro {
+        (@impl $e:expr) => {
+            format!("other: {}", $e)
+        };
+        ($e:expr) => {
+            internal_path_other_macro!(@impl $e)
+        };
+    }
+
+    mod nested {
+        #[macro_export]
+        macro_rules! internal_path_nested_macro {
+            (@impl $e:expr) => {
+                format!("nested: {}", $e)
+            };
+            ($e:expr) => {
+                internal_path_nested_macro!(@impl $e)
+            };
+        }
+    }
+}
+
+#[macro_export]
+macro_rules! qualify_helper {
+    ($e:expr) => {
+        format!("<{}>", $e)
+    };
+}
+
+#[clean_docs(qualify = [qualify_helper], always = true)]
+#[macro_export]
+macro_rules! qualify_macro {
+    (@impl $e:expr) => {
+        qualify_helper!($e)
+    };
+    ($e:expr) => {
+        qualify_macro!(@impl $e)
+    };
+}
+
+#[clean_docs(local_inner_macros, qualify = [qualify_helper], always = true)]
+#[macro_export]
+macro_rules! local_inner_macros_macro {
+    (@impl $e:expr) => {
+        qualify_helper!($e)
+    };
+    ($e:expr) => {
+        local_inner_macros_macro!(@impl $e)
+    };
+}
+
+#[clean_docs(split, always = true)]
+#[macro_export]
+macro_rules! split_macro {
+    (@parse [$($acc:tt)*]) => {
+        split_macro!(@emit $($acc)*)
+    };
+    (@parse [$($acc:tt)*] $x:literal $($rest:tt)*) => {
+        split_macro!(@parse [$($acc)* $x] $($rest)*)
+    };
+    (@emit $($x:literal)*) => {
+        vec![$($x),*]
+    };
+    ($($x:literal)*) => {
+        split_macro!(@parse [] $($x)*)
+    };
+}
+
+#[clean_docs(split, always = true)]
+#[macro_export]
+macro_rules! split_groups_macro {
+    (@step[0] $e:expr) => {
+        split_groups_macro!(@step[1] $e * 10)
+    };
+    (@step[1] $e:expr) => {
+        split_groups_macro!(@step[2] $e + 1)
+    };
+    (@step[$n:tt] $e:expr) => {
+        $e
+    };
+    ($e:expr) => {
+        split_groups_macro!(@step[0] $e)
+    };
+}
+
+#[clean_docs(seal, always = true)]
+#[macro_export]
+macro_rules! sealed_macro {
+    (@impl $e:expr) => {
+        format!("[{}]", $e)
+    };
+    ($e:expr) => {
+        sealed_macro!(@impl $e)
+    };
+}
+
+#[clean_docs(warn_direct, always = true)]
+#[macro_export]
+macro_rules! warn_direct_macro {
+    (@impl $e:expr) => {
+        format!("({})", $e)
+    };
+    ($e:expr) => {
+        warn_direct_macro!(@impl $e)
+    };
+}
+
+#[clean_docs(always = true)]
+#[macro_export]
+macro_rules! calls_b_macro {
+    (@impl $e:expr) => {
+        format!("b: {}", $e)
+    };
+    ($e:expr) => {
+        calls_b_macro!(@impl $e)
+    };
+}
+
+#[clean_docs(calls(calls_b_macro), always = true)]
+#[macro_export]
+macro_rules! calls_a_macro {
+    (@impl $e:expr) => {
+        calls_b_macro!(@impl $e)
+    };
+    ($e:expr) => {
+        calls_a_macro!(@impl $e)
+    };
+}
+
+clean_macro_docs::clean_docs_family! {
+    #![clean_docs(always)]
+
+    #[internal]
+    macro_rules! __family_macros {
+        (@fmt $e:expr) => {
+            format!("{}", $e)
+        };
+        (@twice $e:expr) => {
+            family_a_macro!(@fmt $e) + &__family_macros!(@fmt $e)
+        };
+    }
+
+    #[macro_export]
+    macro_rules! family_a_macro {
+        ($e:expr) => {
+            family_a_macro!(@fmt $e)
+        };
+    }
+
+    #[macro_export]
+    macro_rules! family_b_macro {
+        ($e:expr) => {
+            family_b_macro!(@twice $e)
+        };
+    }
+}
+
+// `$` is passed as `$d`, since `macro_rules!` can't escape it
+macro_rules! make_generated_macro {
+    ($d:tt $name:ident, $body:block) => {
+        #[clean_docs(always = true)]
+        #[macro_export]
+        macro_rules! $name {
+            (@impl $d e:expr) => {
+                format!("{}{}", $d e, $body)
+            };
+            ($d e:expr) => {
+                $crate::$name!(@impl $d e)
+            };
+        }
+    };
+}
+
+make_generated_macro!($ generated_macro, { "!" });
+
 #[clean_docs(always = true)]
 #[macro_export]
 macro_rules! multiple_internal_macro {
@@ -84,6 +366,150 @@ macro_rules! back_and_forth_macro {
     };
 }
 
+#[clean_docs(cfg = "doc")]
+#[macro_export]
+macro_rules! cfg_macro {
+    (@impl $e:expr) => {
+        format!("{}", $e)
+    };
+    ($e:expr) => {
+        cfg_macro!(@impl $e)
+    };
+}
+
+#[clean_docs(mode = "always")]
+#[macro_export]
+macro_rules! mode_always_macro {
+    (@impl $e:expr) => {
+        format!("{}", $e)
+    };
+    ($e:expr) => {
+        mode_always_macro!(@impl $e)
+    };
+}
+
+#[clean_docs(always = true)]
+mod module {
+    #[macro_export]
+    macro_rules! module_macro {
+        (@impl $e:expr) => {
+            format!("{}", $e)
+        };
+        ($e:expr) => {
+            module_macro!(@impl $e)
+        };
+    }
+
+    #[clean_docs(impl = "#internal")]
+    #[macro_export]
+    macro_rules! module_custom_impl_macro {
+        (#internal $e:expr) => {
+            format!("{}", $e)
+        };
+        ($e:expr) => {
+            module_custom_impl_macro!(#internal $e)
+        };
+    }
+
+    #[test]
+    fn module() {
+        assert_eq!(module_macro!(54321), "54321");
+        assert_eq!(module_custom_impl_macro!(54321), "54321");
+    }
+}
+
+// Alternative definitions of a macro don't collide
+#[clean_docs(always = true)]
+mod alternatives {
+    #[cfg(unix)]
+    #[macro_export]
+    macro_rules! alternative_macro {
+        (@impl $e:expr) => {
+            format!("unix: {}", $e)
+        };
+        ($e:expr) => {
+            alternative_macro!(@impl $e)
+        };
+    }
+
+    #[cfg(not(unix))]
+    #[macro_export]
+    macro_rules! alternative_macro {
+        (@impl $e:expr) => {
+            format!("other: {}", $e)
+        };
+        ($e:expr) => {
+            alternative_macro!(@impl $e)
+        };
+    }
+
+    #[test]
+    fn alternatives() {
+        let expected = if cfg!(unix) { "unix: 54321" } else { "other: 54321" };
+        assert_eq!(alternative_macro!(54321), expected);
+    }
+}
+
+mod local {
+    use clean_macro_docs::clean_docs;
+
+    #[clean_docs(local = "reexport", always = true)]
+    #[macro_export]
+    macro_rules! local_reexport_macro {
+        (@impl $e:expr) => {
+            format!("{}", $e)
+        };
+        ($e:expr) => {
+            local_reexport_macro!(@impl $e)
+        };
+    }
+
+    #[test]
+    fn local_reexport() {
+        assert_eq!(crate::local::local_reexport_macro!(@impl 54321), "54321");
+        assert_eq!(local_reexport_macro!(54321), "54321");
+    }
+}
+
+mod not_exported {
+    use clean_macro_docs::clean_docs;
+
+    #[clean_docs(always = true)]
+    macro_rules! not_exported_macro {
+        (@impl $e:expr) => {
+            format!("{}", $e)
+        };
+        ($e:expr) => {
+            $crate::not_exported::not_exported_macro!(@impl $e)
+        };
+    }
+
+    pub(crate) use not_exported_macro;
+
+    #[clean_docs(always = true)]
+    macro_rules! not_exported_bare_macro {
+        (@impl $e:expr) => {
+            format!("{}", $e)
+        };
+        ($e:expr) => {
+            not_exported_bare_macro!(@impl $e)
+        };
+    }
+
+    pub(crate) use not_exported_bare_macro;
+}
+
+// Calls a macro that isn't exported from another module, where only the
+// imported macro is in scope
+mod not_exported_caller {
+    use crate::not_exported::not_exported_bare_macro;
+
+    #[test]
+    fn not_exported_bare() {
+        assert_eq!(not_exported_bare_macro!(54321), "54321");
+    }
+}
+
 #[test]
 fn simple() {
     assert_eq!(simple_macro!(54321), "54321");
@@ -94,6 +520,87 @@ fn custom_impl() {
     assert_eq!(custom_impl_macro!(54321), "54321");
 }
 
+#[test]
+fn multiple_markers() {
+    assert_eq!(multiple_markers_macro!(54321), "54321");
+}
+
+#[test]
+fn marker_kinds() {
+    assert_eq!(marker_kinds_macro!(54321), "54321");
+}
+
+#[test]
+fn annotated() {
+    assert_eq!(annotated_macro!(54321), "verbose: 54321");
+    assert_eq!(annotated_macro!(@verbose 54321), "verbose: 54321");
+}
+
+#[test]
+fn template() {
+    assert_eq!(template_macro!(54321), "54321");
+}
+
+#[test]
+fn raw_ident() {
+    assert_eq!(r#match!(54321), "54321");
+}
+
+#[test]
+fn rename() {
+    assert_eq!(rename_a_macro!(54321), "a: 54321");
+    assert_eq!(rename_b_macro!(54321), "b: 54321");
+}
+
+#[test]
+fn internal_path() {
+    assert_eq!(internal_path_macro!(54321), "54321");
+    assert_eq!(internal_path_other_macro!(54321), "other: 54321");
+    assert_eq!(internal_path_nested_macro!(54321), "nested: 54321");
+}
+
+#[test]
+fn qualify() {
+    assert_eq!(qualify_macro!(54321), "<54321>");
+}
+
+#[test]
+fn local_inner_macros() {
+    assert_eq!(local_inner_macros_macro!(54321), "<54321>");
+}
+
+#[test]
+fn split() {
+    assert_eq!(split_macro!(5 4 3 2 1), [5, 4, 3, 2, 1]);
+    assert_eq!(split_groups_macro!(5), 51);
+}
+
+#[test]
+fn seal() {
+    assert_eq!(sealed_macro!(54321), "[54321]");
+}
+
+#[test]
+fn warn_direct() {
+    assert_eq!(warn_direct_macro!(54321), "(54321)");
+}
+
+#[test]
+fn calls() {
+    assert_eq!(calls_a_macro!(54321), "b: 54321");
+}
+
+#[test]
+fn family() {
+    assert_eq!(family_a_macro!(54321), "54321");
+    assert_eq!(family_b_macro!(54321), "5432154321");
+}
+
+#[test]
+fn generated() {
+    assert_eq!(generated_macro!(54321), "54321!");
+}
+
 #[test]
 fn multiple_internal() {
     assert_eq!(multiple_internal_macro!(54321), "-> [54321] <-");
@@ -115,3 +622,19 @@ fn no_internal() {
 fn back_and_forth() {
     assert_eq!(back_and_forth_macro!([[3]]), ((3, 3), (3, 3)));
 }
+
+#[test]
+fn cfg() {
+    assert_eq!(cfg_macro!(54321), "54321");
+}
+
+#[test]
+fn mode_always() {
+    assert_eq!(mode_always_macro!(54321), "54321");
+}
+
+#[test]
+fn not_exported() {
+    assert_eq!(not_exported::not_exported_macro!(54321), "54321");
+}
+